@@ -1,15 +1,35 @@
-use crate::commands::Command;
+use crate::commands::{AdminCommand, Command};
 use crate::common::conversation;
-use crate::database::Database;
-use crate::maps::Maps;
+use crate::database::{Database, DatabaseError};
+use crate::defines::TeloxideResult;
+use crate::dialogue_storage::MongoStorage;
+use crate::gazetteer_geocoder::GazetteerGeocoder;
+use crate::maps::{Geocoder, Maps};
+use crate::memory_backend::MemoryBackend;
+use crate::mongo_backend::MongoBackend;
+use crate::nominatim_geocoder::NominatimGeocoder;
+use crate::resilient_geocoder::{ResilientGeocoder, RetryPolicy};
+use crate::sqlite_backend::SqliteBackend;
 use crate::state::State;
+use crate::yandex_geocoder::YandexGeocoder;
 use crate::*;
+use mongodb::options::ClientOptions;
 use std::error::Error;
+use std::ops::ControlFlow;
 use std::sync::Arc;
-use teloxide::dispatching::dialogue::InMemStorage;
+use std::time::Duration;
+use teloxide::dispatching::dialogue::serializer::Json;
+use teloxide::dispatching::dialogue::{ErasedStorage, InMemStorage, SqliteStorage, Storage};
 use teloxide::dispatching::{dialogue, UpdateHandler};
 use teloxide::dptree::deps;
+use teloxide::dptree::di::{DependencyMap, DependencySupplier};
+use teloxide::dptree::Cont;
 use teloxide::prelude::*;
+use teloxide::types::{BotCommand, BotCommandScope, Recipient};
+use teloxide::utils::command::BotCommands;
+
+/// Command line argument which copies the MongoDB data into the configured storage instead of running the bot
+const COPY_FROM_MONGODB: &str = "copy-from-mongodb";
 
 pub struct DeafBot;
 
@@ -20,33 +40,360 @@ impl DeafBot {
         log::info!("initializing configuration");
         let config = Configuration::init();
 
+        log::info!("connecting to mongodb");
+        let client = config.mongodb.client().await;
+
+        if std::env::args().nth(1).as_deref() == Some(COPY_FROM_MONGODB) {
+            return Self::copy_from_mongodb(&config, &client).await;
+        }
+
         log::info!("initializing database");
-        let database = Database::init().await;
-        let maps = Maps::init(config.yandex_maps_api_key.clone()).await;
+        let database = Self::database(&config, &client).await;
+        let maps = Maps::new(
+            database.clone(),
+            Self::geocoders(&config),
+            config.location_cache_ttl(),
+            config.location_cache_not_found_ttl(),
+        );
+
+        Self::run(config, client, database, maps).await;
+    }
+
+    async fn database(config: &Configuration, client: &mongodb::Client) -> Arc<Database> {
+        match &config.storage {
+            StorageKind::MongoDb => {
+                let backend = MongoBackend::new(client.clone(), &config.mongodb.database);
+                backend.migrate().await;
+                backend.create_indexes(config.like_ttl(), config.view_ttl()).await
+                    .expect("Unable to create TTL indexes");
+                Database::new(backend)
+            }
+            StorageKind::Sqlite { path } => {
+                let backend = SqliteBackend::open(path).await;
+                backend.migrate().await;
+                backend.expire_after(config.like_ttl(), config.view_ttl());
+                Database::new(backend)
+            }
+            StorageKind::InMemory => {
+                log::warn!("in-memory storage is used, all the data is lost after a restart");
+                Database::new(MemoryBackend::new())
+            }
+        }
+    }
 
-        Self::run(config, database, maps).await;
+    /// Builds the geocoders in the order they are asked, the online ones are retried and rested during outages
+    fn geocoders(config: &Configuration) -> Vec<Arc<dyn Geocoder>> {
+        config.geocoders.iter()
+            .enumerate()
+            .map(|(index, kind)| -> Arc<dyn Geocoder> {
+                let settings = &config.geocoding;
+
+                match kind {
+                    GeocoderKind::Gazetteer { path } => {
+                        let is_last = config.geocoders[index + 1..].iter()
+                            .all(|next| matches!(next, GeocoderKind::Gazetteer { .. }));
+
+                        GazetteerGeocoder::open(path.as_deref(), is_last)
+                    }
+                    GeocoderKind::Yandex { url, api_key } => ResilientGeocoder::wrap(
+                        YandexGeocoder::new(url.clone(), api_key.clone(), settings.timeout),
+                        settings.retry_policy.clone(),
+                    ),
+                    GeocoderKind::Nominatim { url, user_agent } => ResilientGeocoder::wrap(
+                        NominatimGeocoder::new(url.clone(), user_agent.clone(), settings.timeout),
+                        settings.retry_policy.clone(),
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// Moves a deployment off MongoDB: copies everything into the SQLite storage from `config.json`
+    async fn copy_from_mongodb(config: &Configuration, client: &mongodb::Client) {
+        let StorageKind::Sqlite { path } = &config.storage else {
+            panic!("`{COPY_FROM_MONGODB}` needs the sqlite storage kind in `config.json`");
+        };
+
+        let source = MongoBackend::new(client.clone(), &config.mongodb.database);
+        let target = SqliteBackend::open(path).await;
+        target.migrate().await;
+
+        log::info!("copying mongodb data into `{path}`");
+        if let Err(error) = source.copy_into(target.as_ref()).await {
+            log::error!("unable to copy mongodb data: {error}");
+            std::process::exit(1);
+        }
     }
 
-    async fn run(config: Arc<Configuration>, database: Arc<Database>, maps: Arc<Maps>) {
+    async fn run(config: Arc<Configuration>, client: mongodb::Client, database: Arc<Database>, maps: Arc<Maps>) {
         let schema = schema();
         let bot = Bot::new(&config.bot_token);
 
-        let state_storage = InMemStorage::<State>::new();
+        log::info!("registering bot commands");
+        Self::register_commands(&bot, &config).await;
+
+        log::info!("initializing dialogue storage");
+        let state_storage = Self::dialogue_storage(&config, &client).await;
 
         log::info!("dispatching the bot");
 
         Dispatcher::builder(bot, schema)
-            .dependencies(deps![state_storage, database, maps])
+            .dependencies(deps![state_storage, database, maps, config])
             .enable_ctrlc_handler()
             .build()
             .dispatch()
             .await;
     }
+
+    /// Admins get their commands in a separate scope so ordinary users never see them
+    async fn register_commands(bot: &Bot, config: &Configuration) {
+        bot.set_my_commands(Command::bot_commands())
+            .await
+            .expect("Unable to register bot commands");
+
+        let admin_commands: Vec<BotCommand> = Command::bot_commands()
+            .into_iter()
+            .chain(AdminCommand::bot_commands())
+            .collect();
+
+        for admin_id in config.admin_ids() {
+            bot.set_my_commands(admin_commands.clone())
+                .scope(BotCommandScope::Chat { chat_id: Recipient::Id(ChatId::from(*admin_id)) })
+                .await
+                .expect("Unable to register admin commands");
+        }
+    }
+
+    async fn dialogue_storage(
+        config: &Configuration,
+        client: &mongodb::Client,
+    ) -> Arc<ErasedStorage<State>> {
+        match &config.dialogue_storage {
+            DialogueStorageKind::InMemory => InMemStorage::<State>::new().erase(),
+            DialogueStorageKind::MongoDb => {
+                MongoStorage::new(client.database(&config.mongodb.database).collection("dialogues")).erase()
+            }
+            DialogueStorageKind::Sqlite { path } => SqliteStorage::open(path, Json)
+                .await
+                .expect("Unable to open sqlite dialogue storage")
+                .erase(),
+        }
+    }
 }
 
 pub struct Configuration {
     bot_token: String,
-    yandex_maps_api_key: String,
+    geocoders: Vec<GeocoderKind>,
+    dialogue_storage: DialogueStorageKind,
+    storage: StorageKind,
+    mongodb: MongoConfiguration,
+    geocoding: GeocodingConfiguration,
+    max_photos: usize,
+    report_threshold: u64,
+    moderator_chat_id: Option<ChatId>,
+    admin_ids: Vec<UserId>,
+    like_ttl: Duration,
+    view_ttl: Duration,
+    location_cache_ttl: Duration,
+    location_cache_not_found_ttl: Duration,
+    utc_offset: chrono::FixedOffset,
+}
+
+/// MongoDB connection settings.
+///
+/// Every value can be overridden with a `DEAFBOT_MONGODB_*` environment variable
+pub struct MongoConfiguration {
+    uri: String,
+    database: String,
+    app_name: String,
+    min_pool_size: Option<u32>,
+    max_pool_size: Option<u32>,
+}
+
+impl MongoConfiguration {
+    fn from_json(json: &serde_json::Value) -> Self {
+        let uri = env_override("DEAFBOT_MONGODB_URI")
+            .or_else(|| json["uri"].as_str().map(str::to_owned))
+            .unwrap_or_else(|| String::from("mongodb://localhost:27017"));
+
+        let database = env_override("DEAFBOT_MONGODB_DATABASE")
+            .or_else(|| json["database"].as_str().map(str::to_owned))
+            .unwrap_or_else(|| String::from("deafbot"));
+
+        let app_name = env_override("DEAFBOT_MONGODB_APP_NAME")
+            .or_else(|| json["app_name"].as_str().map(str::to_owned))
+            .unwrap_or_else(|| String::from("DeafBot"));
+
+        let min_pool_size = env_override("DEAFBOT_MONGODB_MIN_POOL_SIZE")
+            .map(|v| v.parse().expect("Unable to parse `DEAFBOT_MONGODB_MIN_POOL_SIZE`"))
+            .or_else(|| json["min_pool_size"].as_u64().map(|v| v as u32));
+
+        let max_pool_size = env_override("DEAFBOT_MONGODB_MAX_POOL_SIZE")
+            .map(|v| v.parse().expect("Unable to parse `DEAFBOT_MONGODB_MAX_POOL_SIZE`"))
+            .or_else(|| json["max_pool_size"].as_u64().map(|v| v as u32));
+
+        Self { uri, database, app_name, min_pool_size, max_pool_size }
+    }
+
+    /// Creates the client shared by [Database] and [Maps].
+    ///
+    /// Authentication and TLS are configured through the connection URI
+    pub async fn client(&self) -> mongodb::Client {
+        let mut options = ClientOptions::parse(&self.uri)
+            .await
+            .expect("Unable to parse mongodb connection uri");
+
+        options.app_name = Some(self.app_name.clone());
+        options.min_pool_size = self.min_pool_size;
+        options.max_pool_size = self.max_pool_size;
+
+        mongodb::Client::with_options(options)
+            .expect("Unable to create mongodb client")
+    }
+}
+
+/// Timeouts and retries of the online geocoders
+pub struct GeocodingConfiguration {
+    timeout: Duration,
+    retry_policy: RetryPolicy,
+}
+
+impl GeocodingConfiguration {
+    fn from_json(json: &serde_json::Value) -> Self {
+        Self {
+            timeout: Duration::from_secs(json["timeout_secs"].as_u64().unwrap_or(5)),
+            retry_policy: RetryPolicy {
+                retries: json["retries"].as_u64().map_or(2, |v| v as u32),
+                retry_delay: Duration::from_millis(json["retry_delay_ms"].as_u64().unwrap_or(300)),
+                failure_threshold: json["failure_threshold"].as_u64().map_or(5, |v| v as u32).max(1),
+                cooldown: Duration::from_secs(json["cooldown_secs"].as_u64().unwrap_or(60)),
+            },
+        }
+    }
+}
+
+fn env_override(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|v| !v.is_empty())
+}
+
+/// Where the dialogue states are kept between updates.
+///
+/// Only [DialogueStorageKind::InMemory] loses them after a restart
+pub enum DialogueStorageKind {
+    InMemory,
+    MongoDb,
+    Sqlite { path: String },
+}
+
+impl DialogueStorageKind {
+    /// Without the `dialogue_storage` settings the dialogues are kept where the data is, in the same sqlite file
+    fn from_json(json: &serde_json::Value, storage: &StorageKind) -> Self {
+        let default_kind = match storage {
+            StorageKind::MongoDb => "mongodb",
+            StorageKind::Sqlite { .. } => "sqlite",
+            StorageKind::InMemory => "in_memory",
+        };
+
+        match json["kind"].as_str().unwrap_or(default_kind) {
+            "in_memory" => Self::InMemory,
+            "mongodb" => Self::MongoDb,
+            "sqlite" => Self::Sqlite {
+                path: match (json["path"].as_str(), storage) {
+                    (Some(path), _) => path.to_owned(),
+                    (None, StorageKind::Sqlite { path }) => path.clone(),
+                    (None, _) => String::from("dialogues.sqlite"),
+                },
+            },
+            kind => panic!("Unknown dialogue storage kind `{kind}` in `config.json`"),
+        }
+    }
+}
+
+/// Where users, profiles and everything related to them are kept.
+///
+/// [StorageKind::Sqlite] keeps everything in a single file for small deployments,
+/// [StorageKind::InMemory] loses all the data after a restart and is meant for local runs
+pub enum StorageKind {
+    MongoDb,
+    Sqlite { path: String },
+    InMemory,
+}
+
+impl StorageKind {
+    fn from_json(json: &serde_json::Value) -> Self {
+        match json["kind"].as_str().unwrap_or("mongodb") {
+            "mongodb" => Self::MongoDb,
+            "sqlite" => Self::Sqlite {
+                path: json["path"].as_str()
+                    .unwrap_or("deafbot.sqlite")
+                    .to_owned(),
+            },
+            "in_memory" => Self::InMemory,
+            kind => panic!("Unknown storage kind `{kind}` in `config.json`"),
+        }
+    }
+}
+
+/// Geocoding provider. Every provider accepts a `url` to point it at a self-hosted or a stub server
+pub enum GeocoderKind {
+    /// Offline list of cities, the bundled one if there is no `path`
+    Gazetteer { path: Option<String> },
+    Yandex { url: String, api_key: String },
+    Nominatim { url: String, user_agent: String },
+}
+
+impl GeocoderKind {
+    /// Without the `geocoders` list the bundled gazetteer is used, followed by Yandex if there is an API key
+    fn list_from_json(json: &serde_json::Value) -> Vec<Self> {
+        let Some(geocoders) = json["geocoders"].as_array() else {
+            let mut geocoders = vec![Self::Gazetteer { path: None }];
+
+            if json["yandex_maps_api_key"].is_string() {
+                geocoders.push(Self::yandex_from_json(&serde_json::Value::Null, json));
+            }
+
+            return geocoders;
+        };
+
+        let geocoders: Vec<_> = geocoders.iter()
+            .map(|geocoder| Self::from_json(geocoder, json))
+            .collect();
+
+        assert!(!geocoders.is_empty(), "`geocoders` list in `config.json` is empty");
+
+        geocoders
+    }
+
+    fn from_json(json: &serde_json::Value, root: &serde_json::Value) -> Self {
+        match json["kind"].as_str().expect("Unable to parse geocoder `kind` value in `config.json`") {
+            "gazetteer" => Self::Gazetteer {
+                path: json["path"].as_str().map(str::to_owned),
+            },
+            "yandex" => Self::yandex_from_json(json, root),
+            "nominatim" => Self::Nominatim {
+                url: json["url"].as_str()
+                    .unwrap_or(nominatim_geocoder::DEFAULT_URL)
+                    .to_owned(),
+                user_agent: json["user_agent"].as_str()
+                    .unwrap_or(nominatim_geocoder::DEFAULT_USER_AGENT)
+                    .to_owned(),
+            },
+            kind => panic!("Unknown geocoder kind `{kind}` in `config.json`"),
+        }
+    }
+
+    /// The key may be set on the provider itself or with the top level `yandex_maps_api_key`
+    fn yandex_from_json(json: &serde_json::Value, root: &serde_json::Value) -> Self {
+        Self::Yandex {
+            url: json["url"].as_str()
+                .unwrap_or(yandex_geocoder::DEFAULT_URL)
+                .to_owned(),
+            api_key: json["api_key"].as_str()
+                .or_else(|| root["yandex_maps_api_key"].as_str())
+                .expect("Unable to parse `yandex_maps_api_key` value in `config.json`")
+                .to_owned(),
+        }
+    }
 }
 
 impl Configuration {
@@ -59,16 +406,91 @@ impl Configuration {
         let json: serde_json::Value = serde_json::from_str(string)
             .expect("Unable to parse `config.json` into valid json");
 
+        let storage = StorageKind::from_json(&json["storage"]);
+
         Self {
             bot_token: json["bot_token"].as_str()
                 .expect("Unable to parse `token` value in `config.json`")
                 .to_owned(),
-            yandex_maps_api_key: json["yandex_maps_api_key"].as_str()
-                .expect("Unable to parse `yandex_maps_api_key` value in `config.json`")
-                .to_owned(),
+            geocoders: GeocoderKind::list_from_json(&json),
+            dialogue_storage: DialogueStorageKind::from_json(&json["dialogue_storage"], &storage),
+            storage,
+            mongodb: MongoConfiguration::from_json(&json["mongodb"]),
+            geocoding: GeocodingConfiguration::from_json(&json["geocoding"]),
+            max_photos: json["max_photos"].as_u64()
+                .map_or(3, |v| v as usize)
+                .clamp(1, profile::MAX_PHOTOS),
+            report_threshold: json["report_threshold"].as_u64().unwrap_or(3),
+            moderator_chat_id: json["moderator_chat_id"].as_i64().map(ChatId),
+            admin_ids: json["admin_ids"].as_array()
+                .map(|ids| ids.iter().filter_map(|id| id.as_u64()).map(UserId).collect())
+                .unwrap_or_default(),
+            like_ttl: Duration::from_secs(json["like_ttl_hours"].as_u64().unwrap_or(72) * 60 * 60),
+            view_ttl: Duration::from_secs(json["view_ttl_hours"].as_u64().unwrap_or(168) * 60 * 60),
+            location_cache_ttl: Duration::from_secs(
+                json["location_cache_ttl_hours"].as_u64().unwrap_or(720) * 60 * 60
+            ),
+            location_cache_not_found_ttl: Duration::from_secs(
+                json["location_cache_not_found_ttl_hours"].as_u64().unwrap_or(24) * 60 * 60
+            ),
+            utc_offset: chrono::FixedOffset::east_opt(json["utc_offset_hours"].as_i64().unwrap_or(3) as i32 * 60 * 60)
+                .expect("Unable to parse `utc_offset_hours` value in `config.json`"),
         }
     }
 
+    /// How many distinct users have to report a profile for it to be hidden from search
+    pub fn report_threshold(&self) -> u64 {
+        self.report_threshold
+    }
+
+    /// Chat where reports are sent to be reviewed by moderators
+    pub fn moderator_chat_id(&self) -> Option<ChatId> {
+        self.moderator_chat_id
+    }
+
+    pub fn is_moderator_chat(&self, chat_id: ChatId) -> bool {
+        self.moderator_chat_id == Some(chat_id)
+    }
+
+    /// How long a like waits for an answer before it expires
+    pub fn like_ttl(&self) -> Duration {
+        self.like_ttl
+    }
+
+    /// How long a viewed profile stays out of the search results
+    pub fn view_ttl(&self) -> Duration {
+        self.view_ttl
+    }
+
+    /// How long a found city is taken from the cache before it's geocoded again
+    pub fn location_cache_ttl(&self) -> Duration {
+        self.location_cache_ttl
+    }
+
+    /// How long a city which wasn't found isn't looked up again
+    pub fn location_cache_not_found_ttl(&self) -> Duration {
+        self.location_cache_not_found_ttl
+    }
+
+    /// Timezone of the audience, Moscow time by default. Days in the statistics start at its midnight
+    pub fn utc_offset(&self) -> chrono::FixedOffset {
+        self.utc_offset
+    }
+
+    /// Users allowed to run [AdminCommand]s
+    pub fn admin_ids(&self) -> &[UserId] {
+        &self.admin_ids
+    }
+
+    pub fn is_admin(&self, user_id: UserId) -> bool {
+        self.admin_ids.contains(&user_id)
+    }
+
+    /// How many photos a profile can have, never more than a Telegram album holds
+    pub fn max_photos(&self) -> usize {
+        self.max_photos
+    }
+
     pub fn read_configuration_file() -> String {
         std::fs::read_to_string("config.json")
             .expect("Unable to read `config.json`")
@@ -76,22 +498,76 @@ impl Configuration {
 }
 
 pub fn schema() -> UpdateHandler<Box<dyn Error + Send + Sync>> {
-    dialogue::enter::<Update, InMemStorage<State>, State, _>()
+    report_database_errors().chain(dialogue_schema())
+}
+
+/// Asks the user to try again when a handler fails because of the database,
+/// instead of leaving them without an answer
+fn report_database_errors() -> UpdateHandler<Box<dyn Error + Send + Sync>> {
+    dptree::from_fn(|deps: DependencyMap, cont: Cont<'static, DependencyMap, TeloxideResult>| async move {
+        let bot: Arc<Bot> = deps.get();
+        let update: Arc<Update> = deps.get();
+
+        match cont(deps).await {
+            ControlFlow::Break(Err(error)) if error.is::<DatabaseError>() => {
+                log::error!("update {} failed: {error}", update.id);
+
+                if let Some(chat) = update.chat() {
+                    if let Err(error) = conversation::send_try_again_later(bot.as_ref().clone(), chat.id).await {
+                        log::error!("unable to report the failure to {}: {error}", chat.id);
+                    }
+                }
+
+                ControlFlow::Break(Ok(()))
+            }
+            result => result,
+        }
+    })
+}
+
+fn dialogue_schema() -> UpdateHandler<Box<dyn Error + Send + Sync>> {
+    dialogue::enter::<Update, ErasedStorage<State>, State, _>()
         .branch(
             Update::filter_message()
+                .branch(
+                    dptree::filter(|msg: Message, config: Arc<Configuration>| {
+                        config.is_moderator_chat(msg.chat.id)
+                    })
+                    .endpoint(conversation::ignore_moderator_chat)
+                )
                 .branch(
                     dptree::filter(|msg: Message| !(msg.chat.is_private() && msg.chat.is_chat()))
                         .endpoint(conversation::bot_works_only_in_chats)
                 )
                 .branch(
                     dptree::filter(|msg: Message| {
-                        msg.from().map_or(false, |user| user.username.is_none())
+                        msg.from().is_some_and(|user| user.username.is_none())
                     })
                     .endpoint(conversation::send_username_is_needed),
                 )
                 .branch(
                     dptree::filter_async(async move |msg: Message, db: Arc<Database>| {
-                        db.get_profile(msg.chat.id).await.is_none()
+                        db.is_banned(msg.chat.id).await.is_ok_and(|banned| banned)
+                    })
+                    .endpoint(conversation::send_banned),
+                )
+                .branch(
+                    dptree::filter(|msg: Message, config: Arc<Configuration>| {
+                        msg.from().is_some_and(|user| config.is_admin(user.id))
+                    })
+                    .branch(
+                        teloxide::filter_command::<AdminCommand, _>()
+                            .endpoint(commands::handle_admin_command),
+                    )
+                    .branch(
+                        dptree::case![State::WritingBroadcast { audience }]
+                            .endpoint(state::catch_broadcast),
+                    ),
+                )
+                .branch(
+                    dptree::filter_async(async move |msg: Message, db: Arc<Database>| {
+                        // A failed lookup is not a missing profile, onboarding must not restart because of it
+                        matches!(db.get_profile(msg.chat.id).await, Ok(None))
                     })
                     .branch(
                         teloxide::filter_command::<Command, _>()
@@ -103,6 +579,10 @@ pub fn schema() -> UpdateHandler<Box<dyn Error + Send + Sync>> {
                             state
                         }]
                         .endpoint(state::build_profile),
+                    )
+                    .branch(
+                        dptree::case![State::ChoosingCity { candidates, purpose, message_id }]
+                            .endpoint(state::retype_city),
                     ),
                 )
                 .branch(
@@ -118,10 +598,31 @@ pub fn schema() -> UpdateHandler<Box<dyn Error + Send + Sync>> {
                                 .endpoint(state::look_at_likes),
                         )
                         .branch(
-                            dptree::case![State::EditingProfile { profile_field, callback_query }]
+                            dptree::case![State::WritingLikeMessage { data }]
+                                .endpoint(state::catch_like_message),
+                        )
+                        .branch(
+                            dptree::case![State::EditingProfile { profile_field, message_id }]
                                 .endpoint(state::edit_profile)
                         )
+                        .branch(
+                            dptree::case![State::EditingPhotos { replace, message_id }]
+                                .endpoint(state::edit_photos)
+                        )
+                        .branch(
+                            dptree::case![State::ChoosingCity { candidates, purpose, message_id }]
+                                .endpoint(state::retype_city)
+                        )
                 ),
         )
-        .branch(Update::filter_callback_query().endpoint(callback::handle))
+        .branch(
+            Update::filter_callback_query()
+                .branch(
+                    dptree::filter_async(async move |q: CallbackQuery, db: Arc<Database>| {
+                        db.is_banned(q.from.id).await.is_ok_and(|banned| banned)
+                    })
+                    .endpoint(conversation::answer_banned),
+                )
+                .branch(dptree::endpoint(callback::handle)),
+        )
 }
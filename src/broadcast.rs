@@ -0,0 +1,256 @@
+use crate::database::Database;
+use crate::profile::{Age, AgeFilter, HearingLevel, Profile, Sex};
+use crate::user::MyUserId;
+use derive_getters::Getters;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::MessageId;
+use teloxide::{ApiError, RequestError};
+use thiserror::Error;
+
+/// Telegram allows about 30 messages per second to different chats, stay a bit below it
+const SEND_INTERVAL: Duration = Duration::from_millis(40);
+
+/// The counts are saved after this many recipients, so a restart doesn't lose them
+const SAVE_PROGRESS_EVERY: usize = 100;
+
+/// Which profiles receive a broadcast, every unset field matches anyone
+#[serde_with_macros::skip_serializing_none]
+#[derive(Debug, Clone, Default, Getters, Serialize, Deserialize)]
+pub struct BroadcastAudience {
+    city: Option<String>,
+    hearing_level: Option<HearingLevel>,
+    sex: Option<Sex>,
+    age: Option<AgeFilter>,
+}
+
+impl BroadcastAudience {
+    /// Parses `key=value` pairs, e.g. `city=Нижний Новгород sex=Female age=18-30`.
+    ///
+    /// Words without `=` belong to the previous value, so city names may contain spaces
+    pub fn parse(args: &str) -> Result<Self, AudienceParseError> {
+        let mut pairs: Vec<(String, String)> = vec![];
+
+        for word in args.split_whitespace() {
+            match (word.split_once('='), pairs.last_mut()) {
+                (Some((key, value)), _) => pairs.push((key.to_lowercase(), value.to_owned())),
+                (None, Some((_, value))) => {
+                    value.push(' ');
+                    value.push_str(word);
+                }
+                (None, None) => return Err(AudienceParseError::UnexpectedWord(word.to_owned())),
+            }
+        }
+
+        let mut audience = Self::default();
+
+        for (key, value) in pairs {
+            let invalid = || AudienceParseError::InvalidValue(key.clone(), value.clone());
+
+            match key.as_str() {
+                "city" => audience.city = Some(value),
+                "hearing" => audience.hearing_level = Some(HearingLevel::from_str(&value).map_err(|_| invalid())?),
+                "sex" => audience.sex = Some(Sex::from_str(&value).map_err(|_| invalid())?),
+                "age" => audience.age = Some(Self::parse_age(&value).ok_or_else(invalid)?),
+                _ => return Err(AudienceParseError::UnknownKey(key)),
+            }
+        }
+
+        Ok(audience)
+    }
+
+    fn parse_age(value: &str) -> Option<AgeFilter> {
+        let (lowest, greatest) = value.split_once('-')?;
+        let (lowest, greatest) = (Age::from_str(lowest).ok()?, Age::from_str(greatest).ok()?);
+
+        (lowest <= greatest).then(|| AgeFilter::new(lowest, greatest))
+    }
+
+    /// Filter for the `profiles` collection
+    pub fn filter(&self) -> Document {
+        let mut options = vec![doc! {}];
+
+        if let Some(city) = self.city.as_ref() {
+            options.push(doc! { "location.actual": city });
+        }
+
+        if let Some(hearing_level) = self.hearing_level {
+            options.push(doc! { "hearing_level": hearing_level });
+        }
+
+        if let Some(sex) = self.sex {
+            options.push(doc! { "sex": sex });
+        }
+
+        if let Some(age) = self.age.as_ref() {
+            options.push(doc! { "age": { "$gte": age.lowest, "$lte": age.greatest } });
+        }
+
+        doc! { "$and": options }
+    }
+
+    /// Tells if [BroadcastAudience::filter] would match the profile
+    pub fn matches(&self, profile: &Profile) -> bool {
+        self.city.as_ref().is_none_or(|city| city == profile.location().actual())
+            && self.hearing_level.is_none_or(|hl| hl == *profile.hearing_level())
+            && self.sex.is_none_or(|sex| sex == *profile.sex())
+            && self.age.as_ref().is_none_or(|af| (af.lowest..=af.greatest).contains(profile.age()))
+    }
+
+    pub fn to_summary(&self) -> String {
+        let mut summary = vec![];
+
+        if let Some(city) = self.city.as_ref() {
+            summary.push(format!("город {city}"));
+        }
+
+        if let Some(hearing_level) = self.hearing_level {
+            summary.push(hearing_level.to_plural_str().to_lowercase());
+        }
+
+        if let Some(sex) = self.sex {
+            summary.push(String::from(match sex {
+                Sex::Male => "парни",
+                Sex::Female => "девушки",
+            }));
+        }
+
+        if let Some(age) = self.age.as_ref() {
+            summary.push(format!("возраст {}–{}", age.lowest, age.greatest));
+        }
+
+        match summary.is_empty() {
+            true => String::from("все пользователи"),
+            false => summary.join(", "),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AudienceParseError {
+    #[error("expected `key=value`, found `{0}`")]
+    UnexpectedWord(String),
+
+    #[error("unknown key `{0}`, expected one of `city`, `hearing`, `sex`, `age`")]
+    UnknownKey(String),
+
+    #[error("invalid value `{1}` for `{0}`")]
+    InvalidValue(String, String),
+}
+
+/// A message copied to every profile of the audience, kept in the `broadcasts` collection
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
+pub struct Broadcast {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    admin: MyUserId,
+    audience: BroadcastAudience,
+    from_chat_id: ChatId,
+    message_id: MessageId,
+    recipients: u64,
+    delivered: u64,
+    blocked: u64,
+    failed: u64,
+    created: mongodb::bson::DateTime,
+    finished: Option<mongodb::bson::DateTime>,
+}
+
+impl Broadcast {
+    pub fn new(
+        admin: impl Into<MyUserId>,
+        audience: BroadcastAudience,
+        from_chat_id: ChatId,
+        message_id: MessageId,
+        recipients: u64,
+    ) -> Self {
+        Self {
+            id: ObjectId::new(),
+            admin: admin.into(),
+            audience,
+            from_chat_id,
+            message_id,
+            recipients,
+            delivered: 0,
+            blocked: 0,
+            failed: 0,
+            created: mongodb::bson::DateTime::now(),
+            finished: None,
+        }
+    }
+}
+
+enum Delivery {
+    Delivered,
+    Blocked,
+    Failed,
+}
+
+/// Sends the broadcast one message at a time, then reports the counts to the admin
+pub async fn run(bot: Bot, db: Arc<Database>, mut broadcast: Broadcast, recipients: Vec<MyUserId>) {
+    log::info!("broadcast `{}` started for {} recipients", broadcast.id, recipients.len());
+
+    let mut interval = tokio::time::interval(SEND_INTERVAL);
+
+    for (sent, recipient) in recipients.into_iter().enumerate() {
+        interval.tick().await;
+
+        match deliver(&bot, &broadcast, recipient).await {
+            Delivery::Delivered => broadcast.delivered += 1,
+            Delivery::Blocked => broadcast.blocked += 1,
+            Delivery::Failed => broadcast.failed += 1,
+        }
+
+        if (sent + 1) % SAVE_PROGRESS_EVERY == 0 {
+            save(&db, &broadcast).await;
+        }
+    }
+
+    broadcast.finished = Some(mongodb::bson::DateTime::now());
+    save(&db, &broadcast).await;
+
+    log::info!(
+        "broadcast `{}` finished: {} delivered, {} blocked, {} failed",
+        broadcast.id, broadcast.delivered, broadcast.blocked, broadcast.failed
+    );
+
+    let _ = bot
+        .send_message(
+            broadcast.admin,
+            format!(
+                "📣 Рассылка завершена\n\nДоставлено: {}\nЗаблокировали бота: {}\nОшибок: {}",
+                broadcast.delivered, broadcast.blocked, broadcast.failed
+            ),
+        )
+        .await;
+}
+
+async fn save(db: &Database, broadcast: &Broadcast) {
+    if let Err(error) = db.update_broadcast(broadcast).await {
+        log::error!("unable to save broadcast `{}`: {error}", broadcast.id);
+    }
+}
+
+async fn deliver(bot: &Bot, broadcast: &Broadcast, recipient: MyUserId) -> Delivery {
+    loop {
+        match bot.copy_message(recipient, broadcast.from_chat_id, broadcast.message_id).await {
+            Ok(_) => return Delivery::Delivered,
+            Err(RequestError::RetryAfter(duration)) => tokio::time::sleep(duration).await,
+            Err(RequestError::Api(
+                ApiError::BotBlocked
+                | ApiError::UserDeactivated
+                | ApiError::BotKicked
+                | ApiError::CantInitiateConversation
+                | ApiError::ChatNotFound,
+            )) => return Delivery::Blocked,
+            Err(error) => {
+                log::warn!("broadcast `{}` to `{recipient}` failed: {error}", broadcast.id);
+                return Delivery::Failed;
+            }
+        }
+    }
+}
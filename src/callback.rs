@@ -1,4 +1,5 @@
 use std::fmt::{Display, Formatter};
+use crate::application::Configuration;
 use crate::database::Database;
 use crate::defines::{BotDialogue, TeloxideResult};
 use crate::error::ParseCallbackDataError;
@@ -6,13 +7,39 @@ use std::str::FromStr;
 use std::sync::Arc;
 use teloxide::prelude::*;
 use crate::perform;
-use crate::profile::HearingLevel;
+use crate::profile::{Age, HearingLevel, ReportReason, ReportStatus, Sex};
+use crate::user::MyUserId;
+use serde::{Deserialize, Serialize};
 
 const EPEM: &str = "EPEM";
 const EPD: &str = "EPD";
 const SHR: &str = "SHR";
 const LED: &str = "LED";
 const FED: &str = "FED";
+const SSM: &str = "SSM";
+const SSE: &str = "SSE";
+const SAR: &str = "SAR";
+const SWM: &str = "SWM";
+const THL: &str = "THL";
+const SMD: &str = "SMD";
+const FSS: &str = "FSS";
+const CDA: &str = "CDA";
+const CAD: &str = "CAD";
+const APH: &str = "APH";
+const RPH: &str = "RPH";
+const MPH: &str = "MPH";
+const DPH: &str = "DPH";
+const BPE: &str = "BPE";
+const RVD: &str = "RVD";
+const RPR: &str = "RPR";
+const RPC: &str = "RPC";
+const MDS: &str = "MDS";
+const MWR: &str = "MWR";
+const MBN: &str = "MBN";
+const BLK: &str = "BLK";
+const BCS: &str = "BCS";
+const BCC: &str = "BCC";
+const CTY: &str = "CTY";
 
 const SEP: &str = ":";
 
@@ -25,6 +52,54 @@ const SEP: &str = ":";
 /// LED     - Leave Empty Description
 ///
 /// FED     - Finish Editing Profile
+///
+/// SSM     - Search Settings Menu
+///
+/// SSE     - Search Settings Edit
+///
+/// SAR     - Set Age Range
+///
+/// SWM     - Set Want to Meet
+///
+/// THL     - Toggle Hearing Level
+///
+/// SMD     - Set Max Distance
+///
+/// FSS     - Finish Search Settings
+///
+/// CDA     - Confirm Deleting Account
+///
+/// CAD     - Cancel Account Deletion
+///
+/// APH     - Add Photo
+///
+/// RPH     - Replace Photo
+///
+/// MPH     - Move Photo up
+///
+/// DPH     - Delete Photo
+///
+/// BPE     - Back to Profile Editing
+///
+/// RVD     - Remove Video
+///
+/// RPR     - Report Profile with a Reason
+///
+/// RPC     - Report Profile Cancel
+///
+/// MDS     - Moderator DiSmisses reports
+///
+/// MWR     - Moderator WaRns the user
+///
+/// MBN     - Moderator BaNs the user
+///
+/// BLK     - BLocK a matched user
+///
+/// BCS     - BroadCast Send
+///
+/// BCC     - BroadCast Cancel
+///
+/// CTY     - choose a CiTY out of the candidates
 #[allow(clippy::upper_case_acronyms)]
 pub enum CallbackData {
     EPEM,
@@ -32,6 +107,30 @@ pub enum CallbackData {
     SHR { hearing_level: HearingLevel },
     LED,
     FED,
+    SSM,
+    SSE { search_option: SearchOption },
+    SAR { range: Option<(Age, Age)> },
+    SWM { sex: Option<Sex> },
+    THL { hearing_level: Option<HearingLevel> },
+    SMD { max_meters: Option<u64> },
+    FSS,
+    CDA,
+    CAD,
+    APH,
+    RPH { index: usize },
+    MPH { index: usize },
+    DPH { index: usize },
+    BPE,
+    RVD,
+    RPR { user_id: MyUserId, reason: ReportReason },
+    RPC,
+    MDS { user_id: MyUserId },
+    MWR { user_id: MyUserId },
+    MBN { user_id: MyUserId },
+    BLK { user_id: MyUserId },
+    BCS,
+    BCC,
+    CTY { index: usize },
 }
 
 impl CallbackData {
@@ -46,6 +145,54 @@ impl CallbackData {
             hearing_level: HearingLevel::from_str(data[0]).unwrap(),
         }
     }
+
+    fn index(data: &[&str]) -> usize {
+        data[0].parse().unwrap()
+    }
+
+    fn user_id(data: &[&str]) -> MyUserId {
+        MyUserId::from_str(data[0]).unwrap()
+    }
+
+    fn from_rpr(data: &[&str]) -> Self {
+        Self::RPR {
+            user_id: Self::user_id(data),
+            reason: ReportReason::from_str(data[1]).unwrap(),
+        }
+    }
+
+    fn from_sse(data: &[&str]) -> Self {
+        Self::SSE {
+            search_option: SearchOption::from_str(data[0]).unwrap(),
+        }
+    }
+
+    fn from_sar(data: &[&str]) -> Self {
+        Self::SAR {
+            range: match data {
+                [lowest, greatest] => Some((lowest.parse().unwrap(), greatest.parse().unwrap())),
+                _ => None,
+            },
+        }
+    }
+
+    fn from_swm(data: &[&str]) -> Self {
+        Self::SWM {
+            sex: data.first().map(|el| Sex::from_str(el).unwrap()),
+        }
+    }
+
+    fn from_thl(data: &[&str]) -> Self {
+        Self::THL {
+            hearing_level: data.first().map(|el| HearingLevel::from_str(el).unwrap()),
+        }
+    }
+
+    fn from_smd(data: &[&str]) -> Self {
+        Self::SMD {
+            max_meters: data.first().map(|el| el.parse().unwrap()),
+        }
+    }
 }
 
 impl From<CallbackData> for String {
@@ -55,7 +202,35 @@ impl From<CallbackData> for String {
             CallbackData::EPD { profile_field } => format!("{EPD}{0}{profile_field}", SEP),
             CallbackData::SHR { hearing_level } => format!("{SHR}{0}{hearing_level}", SEP),
             CallbackData::LED => String::from(LED),
-            CallbackData::FED => String::from(FED)
+            CallbackData::FED => String::from(FED),
+            CallbackData::SSM => String::from(SSM),
+            CallbackData::SSE { search_option } => format!("{SSE}{0}{search_option}", SEP),
+            CallbackData::SAR { range: Some((lowest, greatest)) } => format!("{SAR}{0}{lowest}{0}{greatest}", SEP),
+            CallbackData::SAR { range: None } => String::from(SAR),
+            CallbackData::SWM { sex: Some(sex) } => format!("{SWM}{0}{sex}", SEP),
+            CallbackData::SWM { sex: None } => String::from(SWM),
+            CallbackData::THL { hearing_level: Some(hearing_level) } => format!("{THL}{0}{hearing_level}", SEP),
+            CallbackData::THL { hearing_level: None } => String::from(THL),
+            CallbackData::SMD { max_meters: Some(max_meters) } => format!("{SMD}{0}{max_meters}", SEP),
+            CallbackData::SMD { max_meters: None } => String::from(SMD),
+            CallbackData::FSS => String::from(FSS),
+            CallbackData::CDA => String::from(CDA),
+            CallbackData::CAD => String::from(CAD),
+            CallbackData::APH => String::from(APH),
+            CallbackData::RPH { index } => format!("{RPH}{0}{index}", SEP),
+            CallbackData::MPH { index } => format!("{MPH}{0}{index}", SEP),
+            CallbackData::DPH { index } => format!("{DPH}{0}{index}", SEP),
+            CallbackData::BPE => String::from(BPE),
+            CallbackData::RVD => String::from(RVD),
+            CallbackData::RPR { user_id, reason } => format!("{RPR}{0}{user_id}{0}{reason}", SEP),
+            CallbackData::RPC => String::from(RPC),
+            CallbackData::MDS { user_id } => format!("{MDS}{0}{user_id}", SEP),
+            CallbackData::MWR { user_id } => format!("{MWR}{0}{user_id}", SEP),
+            CallbackData::MBN { user_id } => format!("{MBN}{0}{user_id}", SEP),
+            CallbackData::BLK { user_id } => format!("{BLK}{0}{user_id}", SEP),
+            CallbackData::BCS => String::from(BCS),
+            CallbackData::BCC => String::from(BCC),
+            CallbackData::CTY { index } => format!("{CTY}{0}{index}", SEP),
         }
     }
 }
@@ -71,6 +246,30 @@ impl FromStr for CallbackData {
             SHR => Ok(Self::from_shr(&split[1..])),
             LED => Ok(Self::LED),
             FED => Ok(Self::FED),
+            SSM => Ok(Self::SSM),
+            SSE => Ok(Self::from_sse(&split[1..])),
+            SAR => Ok(Self::from_sar(&split[1..])),
+            SWM => Ok(Self::from_swm(&split[1..])),
+            THL => Ok(Self::from_thl(&split[1..])),
+            SMD => Ok(Self::from_smd(&split[1..])),
+            FSS => Ok(Self::FSS),
+            CDA => Ok(Self::CDA),
+            CAD => Ok(Self::CAD),
+            APH => Ok(Self::APH),
+            RPH => Ok(Self::RPH { index: Self::index(&split[1..]) }),
+            MPH => Ok(Self::MPH { index: Self::index(&split[1..]) }),
+            DPH => Ok(Self::DPH { index: Self::index(&split[1..]) }),
+            BPE => Ok(Self::BPE),
+            RVD => Ok(Self::RVD),
+            RPR => Ok(Self::from_rpr(&split[1..])),
+            RPC => Ok(Self::RPC),
+            MDS => Ok(Self::MDS { user_id: Self::user_id(&split[1..]) }),
+            MWR => Ok(Self::MWR { user_id: Self::user_id(&split[1..]) }),
+            MBN => Ok(Self::MBN { user_id: Self::user_id(&split[1..]) }),
+            BLK => Ok(Self::BLK { user_id: Self::user_id(&split[1..]) }),
+            BCS => Ok(Self::BCS),
+            BCC => Ok(Self::BCC),
+            CTY => Ok(Self::CTY { index: Self::index(&split[1..]) }),
             _ => Err(ParseCallbackDataError::UnknownCallbackCode {
                 code: split[0].to_owned(),
             }),
@@ -82,6 +281,7 @@ pub async fn handle(
     bot: Bot,
     q: CallbackQuery,
     db: Arc<Database>,
+    config: Arc<Configuration>,
     dialogue: BotDialogue,
 ) -> TeloxideResult {
     bot.answer_callback_query(&q.id).await?;
@@ -94,23 +294,58 @@ pub async fn handle(
     match callback_data {
         CallbackData::EPEM => perform::enter_profile_editing_mode(bot, q).await?,
         CallbackData::EPD { profile_field } => {
-            perform::set_profile_editing_handler(bot, db, q, dialogue, profile_field).await?;
+            perform::set_profile_editing_handler(bot, db, config, q, dialogue, profile_field).await?;
         }
         CallbackData::SHR { hearing_level } => perform::set_hearing_level(bot, db, q, hearing_level).await?,
         CallbackData::LED => perform::leave_empty_description(bot, db, q).await?,
         CallbackData::FED => perform::finish_editing_profile(bot, db, dialogue, q).await?,
+        CallbackData::SSM => perform::show_search_settings(bot, db, dialogue, q).await?,
+        CallbackData::SSE { search_option } => {
+            perform::set_search_settings_handler(bot, db, dialogue, q, search_option).await?;
+        }
+        CallbackData::SAR { range } => perform::set_search_age(bot, db, dialogue, q, range).await?,
+        CallbackData::SWM { sex } => perform::set_search_sex(bot, db, dialogue, q, sex).await?,
+        CallbackData::THL { hearing_level } => perform::toggle_search_hearing_level(bot, db, dialogue, q, hearing_level).await?,
+        CallbackData::SMD { max_meters } => perform::set_search_distance(bot, db, dialogue, q, max_meters).await?,
+        CallbackData::FSS => perform::finish_search_settings(bot, db, dialogue, q).await?,
+        CallbackData::CDA => perform::delete_account(bot, db, dialogue, q).await?,
+        CallbackData::CAD => perform::cancel_account_deletion(bot, q).await?,
+        CallbackData::APH => perform::request_photo(bot, q, dialogue, None).await?,
+        CallbackData::RPH { index } => perform::request_photo(bot, q, dialogue, Some(index)).await?,
+        CallbackData::MPH { index } => perform::move_photo_up(bot, db, config, dialogue, q, index).await?,
+        CallbackData::DPH { index } => perform::delete_photo(bot, db, config, dialogue, q, index).await?,
+        CallbackData::BPE => perform::back_to_profile_editing(bot, q).await?,
+        CallbackData::RVD => perform::remove_video(bot, db, dialogue, q).await?,
+        CallbackData::RPR { user_id, reason } => {
+            perform::report_profile(bot, db, config, dialogue, q, user_id, reason).await?;
+        }
+        CallbackData::RPC => perform::cancel_report(bot, q).await?,
+        CallbackData::MDS { user_id } => {
+            perform::moderate_report(bot, db, config, q, user_id, ReportStatus::Dismissed).await?;
+        }
+        CallbackData::MWR { user_id } => {
+            perform::moderate_report(bot, db, config, q, user_id, ReportStatus::Warned).await?;
+        }
+        CallbackData::MBN { user_id } => {
+            perform::moderate_report(bot, db, config, q, user_id, ReportStatus::Banned).await?;
+        }
+        CallbackData::BLK { user_id } => perform::block_match(bot, db, q, user_id).await?,
+        CallbackData::BCS => perform::send_broadcast(bot, db, config, dialogue, q).await?,
+        CallbackData::BCC => perform::cancel_broadcast(bot, dialogue, q).await?,
+        CallbackData::CTY { index } => perform::choose_city(bot, db, dialogue, q, index).await?,
     }
 
     Ok(())
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ProfileField {
     Name,
     Age,
     City,
     HearingLevel,
     Description,
+    Video,
     Photo,
 }
 
@@ -122,6 +357,7 @@ impl ProfileField {
             Self::City => "City",
             Self::HearingLevel => "HearingLevel",
             Self::Description => "Description",
+            Self::Video => "Video",
             Self::Photo => "Photo",
         }
     }
@@ -143,8 +379,48 @@ impl FromStr for ProfileField {
             "City" => Ok(Self::City),
             "HearingLevel" => Ok(Self::HearingLevel),
             "Description" => Ok(Self::Description),
+            "Video" => Ok(Self::Video),
             "Photo" => Ok(Self::Photo),
             _ => Err(())
         }
     }
 }
+
+#[derive(Debug, Clone)]
+pub enum SearchOption {
+    Age,
+    WantToMeet,
+    HearingLevel,
+    Distance,
+}
+
+impl SearchOption {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::Age => "Age",
+            Self::WantToMeet => "WantToMeet",
+            Self::HearingLevel => "HearingLevel",
+            Self::Distance => "Distance",
+        }
+    }
+}
+
+impl Display for SearchOption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+impl FromStr for SearchOption {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Age" => Ok(Self::Age),
+            "WantToMeet" => Ok(Self::WantToMeet),
+            "HearingLevel" => Ok(Self::HearingLevel),
+            "Distance" => Ok(Self::Distance),
+            _ => Err(())
+        }
+    }
+}
@@ -1,18 +1,51 @@
+use crate::application::Configuration;
 use crate::common::conversation;
+use crate::database::Database;
+use crate::maps::Maps;
+use crate::perform;
 use crate::defines::{BotDialogue, TeloxideResult};
 use crate::profile::Profile;
 use crate::state::State;
 use teloxide::macros::BotCommands;
+use std::sync::Arc;
 use teloxide::prelude::*;
 
 #[derive(Debug, Clone, BotCommands)]
 #[command(rename_rule = "lowercase", parse_with = "split")]
 pub enum Command {
+    #[command(description = "открыть меню")]
     Start,
+    #[command(description = "скрыть анкету из поиска")]
+    Pause,
+    #[command(description = "вернуть анкету в поиск")]
+    Resume,
+    #[command(description = "удалить все мои данные")]
+    Delete,
+    #[command(description = "выгрузить мои данные")]
+    MyData,
+}
+
+/// Commands available only to the users listed in `admin_ids` of `config.json`
+#[derive(Debug, Clone, BotCommands)]
+#[command(rename_rule = "lowercase")]
+pub enum AdminCommand {
+    #[command(description = "статистика бота")]
+    Stats,
+    #[command(description = "забанить по ID или username")]
+    Ban(String),
+    #[command(description = "разбанить по ID или username")]
+    Unban(String),
+    #[command(description = "показать анкету по ID или username")]
+    Profile(String),
+    #[command(description = "скрыть анкету из поиска по ID или username")]
+    Hide(String),
+    #[command(description = "рассылка, например: city=Москва hearing=CompletelyDeaf sex=Female age=18-30")]
+    Broadcast(String),
 }
 
 pub async fn handle_user_without_profile(
     bot: Bot,
+    db: Arc<Database>,
     dialogue: BotDialogue,
     msg: Message,
     command: Command,
@@ -28,13 +61,48 @@ pub async fn handle_user_without_profile(
                 })
                 .await?;
         }
+        Command::Delete => perform::request_account_deletion(bot, msg.chat.id).await?,
+        Command::MyData => perform::send_user_data(bot, db, msg.chat.id).await?,
+        Command::Pause | Command::Resume => {}
     }
     Ok(())
 }
 
-pub async fn handle_command(bot: Bot, msg: Message, command: Command) -> TeloxideResult {
+pub async fn handle_command(
+    bot: Bot,
+    db: Arc<Database>,
+    config: Arc<Configuration>,
+    msg: Message,
+    command: Command,
+) -> TeloxideResult {
+    match command {
+        Command::Start => conversation::send_menu(bot, db, msg.chat.id).await?,
+        Command::Pause => perform::set_profile_visibility(bot, db, config, msg.chat.id, false).await?,
+        Command::Resume => perform::set_profile_visibility(bot, db, config, msg.chat.id, true).await?,
+        Command::Delete => perform::request_account_deletion(bot, msg.chat.id).await?,
+        Command::MyData => perform::send_user_data(bot, db, msg.chat.id).await?,
+    }
+    Ok(())
+}
+
+pub async fn handle_admin_command(
+    bot: Bot,
+    db: Arc<Database>,
+    maps: Arc<Maps>,
+    config: Arc<Configuration>,
+    dialogue: BotDialogue,
+    msg: Message,
+    command: AdminCommand,
+) -> TeloxideResult {
+    let chat_id = msg.chat.id;
+
     match command {
-        Command::Start => conversation::send_menu(bot, msg.chat.id).await?,
+        AdminCommand::Stats => perform::send_stats(bot, db, maps, config, chat_id).await?,
+        AdminCommand::Ban(target) => perform::admin_ban(bot, db, chat_id, &target).await?,
+        AdminCommand::Unban(target) => perform::admin_unban(bot, db, chat_id, &target).await?,
+        AdminCommand::Profile(target) => perform::admin_show_profile(bot, db, chat_id, &target).await?,
+        AdminCommand::Hide(target) => perform::admin_hide_profile(bot, db, chat_id, &target).await?,
+        AdminCommand::Broadcast(args) => perform::start_broadcast(bot, db, dialogue, chat_id, &args).await?,
     }
     Ok(())
 }
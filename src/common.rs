@@ -1,71 +1,113 @@
+use crate::application::Configuration;
 use crate::common::keyboard::{MakeKeyboard, Menu};
 use crate::database::*;
 use crate::defines::{TeloxideResult};
-use crate::profile::{Profile, ProfileBuilder};
+use crate::profile::{Like, LikeMessage, Profile, ProfileBuilder};
 use crate::user::{MyUserId, User};
 use std::fmt::{Debug};
 use std::sync::Arc;
+use std::time::Duration;
 use teloxide::prelude::*;
-use teloxide::types::ParseMode;
+use teloxide::types::{KeyboardMarkup, ParseMode};
 
 pub async fn finish_profile_creation(
     bot: Bot,
     db: Arc<Database>,
+    config: Arc<Configuration>,
     user: &User,
     profile_builder: ProfileBuilder,
 ) -> TeloxideResult {
     let profile = profile_builder.build();
 
-    if db.get_user(*user.id()).await.is_none() {
-        db.add_user(user).await;
+    if db.get_user(*user.id()).await?.is_none() {
+        db.add_user(user).await?;
     }
 
-    db.add_profile(&profile).await;
+    db.add_profile(&profile).await?;
+
+    // Reports and bans outlive /delete, a new profile of the same user waits for the moderators too
+    let on_hold = is_on_hold(&db, &config, *profile.id()).await?;
+
+    if on_hold {
+        db.set_profile_visibility(*profile.id(), false).await?;
+    }
 
     bot.send_message(*profile.id(), "Готово. Вот твоя анкета:")
-        .reply_markup(Menu::keyboard())
+        .reply_markup(Menu::keyboard_for(!on_hold))
         .await?;
 
+    if on_hold {
+        bot.send_message(*profile.id(), "🛡 На тебя поступали жалобы, анкета появится в поиске после проверки модераторами")
+            .await?;
+    }
+
     conversation::send_profile(bot, *profile.id(), &profile).await?;
 
     Ok(())
 }
 
-pub async fn next_suggestion(db: Arc<Database>, user_id: impl Into<MyUserId>) -> Option<Profile> {
-    let user_id = user_id.into();
+/// Whether the user is banned or reported by enough users, such profiles stay hidden until moderators look at them
+pub async fn is_on_hold(db: &Database, config: &Configuration, user_id: MyUserId) -> DatabaseResult<bool> {
+    Ok(db.is_banned(user_id).await? || db.count_reporters(user_id).await? >= config.report_threshold())
+}
 
-    let user_profile = db.get_profile(user_id).await.unwrap();
-    let viewed_profiles = db.get_user_views(user_id).await;
+pub async fn next_suggestion(db: Arc<Database>, user_id: impl Into<MyUserId>) -> DatabaseResult<Option<Profile>> {
+    let user_id = user_id.into();
 
-    let searching_filter = user_profile.search_filter(&viewed_profiles);
+    let Some(user_profile) = db.get_profile(user_id).await? else {
+        return Ok(None);
+    };
+    let viewed_profiles = db.get_user_views(user_id).await?;
+    let blocked_users = db.get_blocked_users(user_id).await?;
 
-    db.filter_profile(searching_filter).await
+    db.find_suggestion(&user_profile, &viewed_profiles, &blocked_users).await
 }
 
-/// Returns [Profile] of a user who liked user with the given _user_id_
+/// Returns [Profile] of a user who liked user with the given _user_id_ together with the [Like]
 ///
 /// Returns _None_ if there is no record of the user's like in the database,
-/// or if the user does not have a profile
+/// or if none of the users who liked have a visible profile and aren't blocked
 pub async fn next_profile_who_have_liked(
     db: Arc<Database>,
     user_id: impl Into<MyUserId>,
-) -> Option<Profile> {
-    let like = db.get_like_to_user(user_id.into()).await?;
-    db.get_profile(*like.from()).await
+) -> DatabaseResult<Option<(Profile, Like)>> {
+    let user_id = user_id.into();
+    let blocked_users = db.get_blocked_users(user_id).await?;
+
+    for like in db.get_likes_to_user(user_id).await? {
+        if blocked_users.contains(like.from()) {
+            continue;
+        }
+
+        match db.get_profile(*like.from()).await? {
+            Some(profile) if *profile.settings().show_up_in_search() => return Ok(Some((profile, like))),
+            _ => continue,
+        }
+    }
+
+    Ok(None)
+}
+
+/// Menu keyboard reminding the user if their profile is hidden from search
+pub async fn menu_keyboard(db: Arc<Database>, user_id: impl Into<MyUserId>) -> DatabaseResult<KeyboardMarkup> {
+    Ok(match db.get_profile(user_id.into()).await? {
+        Some(profile) => Menu::keyboard_for(*profile.settings().show_up_in_search()),
+        None => Menu::keyboard(),
+    })
 }
 
 pub async fn check_for_match(
     db: Arc<Database>,
     from: impl Into<MyUserId>,
     to: impl Into<MyUserId>,
-) -> CheckForMatchResult {
+) -> DatabaseResult<CheckForMatchResult> {
     let (from, to) = (from.into(), to.into());
 
-    db.find_like(from, to)
-        .await
+    Ok(db.find_like(from, to)
+        .await?
         .map_or(CheckForMatchResult::DontMatch, |_| {
             CheckForMatchResult::Match
-        })
+        }))
 }
 
 pub mod text {
@@ -81,8 +123,9 @@ pub enum CheckForMatchResult {
 
 pub mod structs {
     use super::*;
+    use serde::{Deserialize, Serialize};
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, Debug, Serialize, Deserialize)]
     pub struct SearchData {
         pub user_profile: Profile,
         pub profile_id: MyUserId,
@@ -91,9 +134,11 @@ pub mod structs {
 
 pub mod conversation {
     use super::*;
+    use crate::common::keyboard::LookingAtProfiles;
     
+    use crate::profile::ProfileVideo;
     use std::error::Error;
-    use teloxide::types::InlineKeyboardMarkup;
+    use teloxide::types::{InlineKeyboardMarkup, InputFile};
 
     pub async fn bot_works_only_in_chats(
         bot: Bot,
@@ -104,6 +149,31 @@ pub mod conversation {
         Ok(())
     }
 
+    /// Moderators talk in their chat freely, the bot only reacts to the inline buttons there
+    pub async fn ignore_moderator_chat() -> TeloxideResult {
+        Ok(())
+    }
+
+    pub async fn send_banned(bot: Bot, msg: Message) -> TeloxideResult {
+        bot.send_message(msg.chat.id, "⛔ Твой аккаунт заблокирован модераторами")
+            .reply_markup(teloxide::types::KeyboardRemove::new())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn answer_banned(bot: Bot, q: CallbackQuery) -> TeloxideResult {
+        bot.answer_callback_query(&q.id)
+            .text("⛔ Твой аккаунт заблокирован модераторами")
+            .await?;
+        Ok(())
+    }
+
+    /// Sent when the request failed on our side, the user did nothing wrong
+    pub async fn send_try_again_later(bot: Bot, chat_id: ChatId) -> TeloxideResult {
+        bot.send_message(chat_id, "😔 Что-то пошло не так. Попробуй ещё раз чуть позже").await?;
+        Ok(())
+    }
+
     pub async fn send_username_is_needed(bot: Bot, msg: Message) -> TeloxideResult {
         bot.parse_mode(ParseMode::Html)
             .send_message(
@@ -115,9 +185,11 @@ pub mod conversation {
         Ok(())
     }
 
-    pub async fn send_menu(bot: Bot, user_id: impl Into<MyUserId>) -> TeloxideResult {
-        bot.send_message(user_id.into(), "🏠 Меню")
-            .reply_markup(Menu::keyboard())
+    pub async fn send_menu(bot: Bot, db: Arc<Database>, user_id: impl Into<MyUserId>) -> TeloxideResult {
+        let user_id = user_id.into();
+
+        bot.send_message(user_id, "🏠 Меню")
+            .reply_markup(menu_keyboard(db, user_id).await?)
             .await?;
         Ok(())
     }
@@ -161,22 +233,37 @@ pub mod conversation {
         user_id: impl Into<MyUserId>,
         profile: &Profile,
     ) -> Result<Message, Box<dyn Error + Send + Sync>> {
+        let user_id = user_id.into();
+
         let message = bot
-            .send_media_group(user_id.into(), profile.to_mediagroup())
+            .send_media_group(user_id, profile.to_mediagroup())
             .await?
             .first()
             .unwrap()
             .clone();
+
+        match profile.video() {
+            Some(ProfileVideo::Video(id)) => {
+                bot.send_video(user_id, InputFile::file_id(id)).await?;
+            }
+            Some(ProfileVideo::VideoNote(id)) => {
+                bot.send_video_note(user_id, InputFile::file_id(id)).await?;
+            }
+            None => {}
+        }
+
         Ok(message)
     }
 
     #[inline]
-    pub async fn send_no_suggestion(bot: Bot, user_id: impl Into<MyUserId>) -> TeloxideResult {
+    pub async fn send_no_suggestion(bot: Bot, db: Arc<Database>, user_id: impl Into<MyUserId>) -> TeloxideResult {
+        let user_id = user_id.into();
+
         bot.send_message(
-            user_id.into(),
+            user_id,
             "Анкет, удовлетворяющих твоим критериям поиска, не найдено",
         )
-        .reply_markup(Menu::keyboard())
+        .reply_markup(menu_keyboard(db, user_id).await?)
         .await?;
         Ok(())
     }
@@ -187,14 +274,59 @@ pub mod conversation {
         user_id: impl Into<MyUserId>,
     ) -> TeloxideResult {
         bot.send_message(user_id.into(), "Лайки закончились, включен режим поиска")
+            .reply_markup(LookingAtProfiles::keyboard())
             .await?;
         Ok(())
     }
+
+    pub async fn send_like_time_left(
+        bot: Bot,
+        user_id: impl Into<MyUserId>,
+        like: &Like,
+        ttl: Duration,
+    ) -> TeloxideResult {
+        let hours = like.time_left(ttl).as_secs() / 60 / 60;
+
+        let text = match hours {
+            0 => String::from("⏳ Лайк сгорит меньше чем через час"),
+            hours if hours < 48 => format!("⏳ Лайк действителен ещё {hours} ч."),
+            hours => format!("⏳ Лайк действителен ещё {} дн.", hours / 24),
+        };
+
+        bot.send_message(user_id.into(), text).await?;
+        Ok(())
+    }
+
+    pub async fn send_like_message(
+        bot: Bot,
+        user_id: impl Into<MyUserId>,
+        message: &LikeMessage,
+    ) -> TeloxideResult {
+        let user_id = user_id.into();
+
+        match message {
+            LikeMessage::Text(text) => {
+                bot.send_message(user_id, format!("💌 Сообщение для тебя:\n\n{text}"))
+                    .await?;
+            }
+            LikeMessage::Sticker(id) => {
+                bot.send_message(user_id, "💌 Сообщение для тебя:").await?;
+                bot.send_sticker(user_id, InputFile::file_id(id)).await?;
+            }
+            LikeMessage::VideoNote(id) => {
+                bot.send_message(user_id, "💌 Сообщение для тебя:").await?;
+                bot.send_video_note(user_id, InputFile::file_id(id)).await?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 pub mod keyboard {
-    use crate::callback::{CallbackData, ProfileField};
-    use crate::profile::Sex;
+    use crate::callback::{CallbackData, ProfileField, SearchOption};
+    use crate::profile::{Age, ReportReason, Sex};
+    use crate::user::MyUserId;
     
     use teloxide::types::{
         ButtonRequest, InlineKeyboardButton, InlineKeyboardMarkup, KeyboardButton, KeyboardMarkup,
@@ -219,6 +351,7 @@ pub mod keyboard {
         pub const WATCH_PROFILES: &str = "🚀 Поиск";
         pub const MY_PROFILE: &str = "⭐ Профиль";
         pub const LIKE: &str = "❤️";
+        pub const LIKE_WITH_MESSAGE: &str = "💌";
         pub const DISLIKE: &str = "👎";
         pub const MENU: &str = "🏠";
         pub const WHO_LIKES_ME: &str = "📩 Лайки";
@@ -230,6 +363,36 @@ pub mod keyboard {
         pub const EDIT_HEARING_LEVEL: &str = "👂 Уровень слуха";
         pub const EDIT_DESCRIPTION: &str = "📝 Описание";
         pub const EDIT_PHOTO: &str = "🖼 Фото";
+        pub const EDIT_VIDEO: &str = "🎬 Видео";
+        pub const SKIP: &str = "Пропустить";
+        pub const REMOVE_VIDEO: &str = "Удалить видео";
+        pub const SEARCH_SETTINGS: &str = "⚙ Настройки поиска";
+        pub const SEARCH_AGE: &str = "🎂 Возраст";
+        pub const SEARCH_SEX: &str = "👫 Кого ищу";
+        pub const SEARCH_DISTANCE: &str = "📍 Расстояние";
+        pub const ANY: &str = "Любой";
+        pub const ONLY_MY_CITY: &str = "Только мой город";
+        pub const DEAF_PLURAL: &str = "Глухие";
+        pub const HEARING_IMPAIRED_PLURAL: &str = "Слабослышащие";
+        pub const HEARING_PLURAL: &str = "Слышащие";
+        pub const BACK: &str = "↩ Назад";
+        pub const PAUSE: &str = "⏸ Скрыть анкету";
+        pub const RESUME: &str = "🙈 Анкета скрыта · Показать";
+        pub const CONFIRM_DELETION: &str = "🗑 Да, удалить всё";
+        pub const CANCEL: &str = "Отмена";
+        pub const CONFIRM_BROADCAST: &str = "📣 Разослать";
+        pub const DONE: &str = "Готово";
+        pub const ADD_PHOTO: &str = "➕ Добавить фото";
+        pub const REPORT: &str = "⚠ Пожаловаться";
+        pub const BLOCK: &str = "🚫 Заблокировать";
+        pub const REPORT_FAKE: &str = "🎭 Фейк";
+        pub const REPORT_ABUSE: &str = "🤬 Оскорбления";
+        pub const REPORT_UNDERAGE: &str = "🔞 Несовершеннолетний";
+        pub const REPORT_SPAM: &str = "📢 Спам или реклама";
+        pub const REPORT_OTHER: &str = "❔ Другое";
+        pub const DISMISS: &str = "✅ Отклонить";
+        pub const WARN: &str = "⚠ Предупредить";
+        pub const BAN: &str = "⛔ Забанить";
     }
 
     pub trait MakeKeyboard {
@@ -269,25 +432,292 @@ pub mod keyboard {
                 [InlineKeyboardButton::callback(EDIT_CITY, CallbackData::EPD { profile_field: ProfileField::City })],
                 [InlineKeyboardButton::callback(EDIT_HEARING_LEVEL, CallbackData::EPD { profile_field: ProfileField::HearingLevel })],
                 [InlineKeyboardButton::callback(EDIT_DESCRIPTION, CallbackData::EPD { profile_field: ProfileField::Description })],
+                [InlineKeyboardButton::callback(EDIT_VIDEO, CallbackData::EPD { profile_field: ProfileField::Video })],
                 [InlineKeyboardButton::callback(EDIT_PHOTO, CallbackData::EPD { profile_field: ProfileField::Photo })],
                 [InlineKeyboardButton::callback(FINISH, CallbackData::FED)],
             ])
         }
     }
 
+    /// Age ranges offered in the search settings
+    pub const SEARCH_AGE_RANGES: [(Age, Age); 6] = [(18, 25), (22, 30), (25, 35), (30, 45), (40, 60), (50, 99)];
+
+    /// Search radiuses in kilometers offered in the search settings
+    pub const SEARCH_DISTANCES_KM: [u64; 5] = [5, 10, 30, 50, 100];
+
+    pub struct SearchSettings;
+
+    impl SearchSettings {
+        pub fn keyboard() -> InlineKeyboardMarkup {
+            InlineKeyboardMarkup::new([
+                [InlineKeyboardButton::callback(SEARCH_AGE, CallbackData::SSE { search_option: SearchOption::Age })],
+                [InlineKeyboardButton::callback(SEARCH_SEX, CallbackData::SSE { search_option: SearchOption::WantToMeet })],
+                [InlineKeyboardButton::callback(EDIT_HEARING_LEVEL, CallbackData::SSE { search_option: SearchOption::HearingLevel })],
+                [InlineKeyboardButton::callback(SEARCH_DISTANCE, CallbackData::SSE { search_option: SearchOption::Distance })],
+                [InlineKeyboardButton::callback(FINISH, CallbackData::FSS)],
+            ])
+        }
+    }
+
+    pub struct SetSearchAge;
+
+    impl SetSearchAge {
+        pub fn keyboard() -> InlineKeyboardMarkup {
+            let mut rows: Vec<Vec<InlineKeyboardButton>> = SEARCH_AGE_RANGES
+                .chunks(2)
+                .map(|chunk| {
+                    chunk
+                        .iter()
+                        .map(|&(lowest, greatest)| {
+                            InlineKeyboardButton::callback(
+                                format!("{lowest}–{greatest}"),
+                                CallbackData::SAR { range: Some((lowest, greatest)) },
+                            )
+                        })
+                        .collect()
+                })
+                .collect();
+
+            rows.push(vec![InlineKeyboardButton::callback(ANY, CallbackData::SAR { range: None })]);
+            rows.push(vec![InlineKeyboardButton::callback(BACK, CallbackData::SSM)]);
+
+            InlineKeyboardMarkup::new(rows)
+        }
+    }
+
+    pub struct SetSearchSex;
+
+    impl SetSearchSex {
+        pub fn keyboard() -> InlineKeyboardMarkup {
+            InlineKeyboardMarkup::new([
+                vec![
+                    InlineKeyboardButton::callback(WANT_A_BOY, CallbackData::SWM { sex: Some(Sex::Male) }),
+                    InlineKeyboardButton::callback(WANT_A_GIRL, CallbackData::SWM { sex: Some(Sex::Female) }),
+                ],
+                vec![InlineKeyboardButton::callback(WHATEVER, CallbackData::SWM { sex: None })],
+                vec![InlineKeyboardButton::callback(BACK, CallbackData::SSM)],
+            ])
+        }
+    }
+
+    pub struct SetSearchHearingLevel;
+
+    impl SetSearchHearingLevel {
+        /// Selected hearing levels are marked with a check mark
+        pub fn keyboard(selected: &[profile::HearingLevel]) -> InlineKeyboardMarkup {
+            let mut rows: Vec<Vec<InlineKeyboardButton>> = enum_iterator::all::<profile::HearingLevel>()
+                .map(|hl| {
+                    let mark = if selected.contains(&hl) { "✅ " } else { "" };
+                    vec![InlineKeyboardButton::callback(
+                        format!("{mark}{}", hl.to_plural_str()),
+                        CallbackData::THL { hearing_level: Some(hl) },
+                    )]
+                })
+                .collect();
+
+            rows.push(vec![InlineKeyboardButton::callback(ANY, CallbackData::THL { hearing_level: None })]);
+            rows.push(vec![InlineKeyboardButton::callback(BACK, CallbackData::SSM)]);
+
+            InlineKeyboardMarkup::new(rows)
+        }
+    }
+
+    pub struct SetSearchDistance;
+
+    impl SetSearchDistance {
+        pub fn keyboard() -> InlineKeyboardMarkup {
+            let mut rows: Vec<Vec<InlineKeyboardButton>> = SEARCH_DISTANCES_KM
+                .iter()
+                .map(|&km| {
+                    vec![InlineKeyboardButton::callback(
+                        format!("до {km} км"),
+                        CallbackData::SMD { max_meters: Some(km * 1000) },
+                    )]
+                })
+                .collect();
+
+            rows.push(vec![InlineKeyboardButton::callback(ONLY_MY_CITY, CallbackData::SMD { max_meters: None })]);
+            rows.push(vec![InlineKeyboardButton::callback(BACK, CallbackData::SSM)]);
+
+            InlineKeyboardMarkup::new(rows)
+        }
+    }
+
+    pub struct EditPhotos;
+
+    impl EditPhotos {
+        /// One row per photo: move it up, replace it or delete it
+        pub fn keyboard(count: usize, max: usize) -> InlineKeyboardMarkup {
+            let mut rows: Vec<Vec<InlineKeyboardButton>> = (0..count)
+                .map(|index| {
+                    let mut row = vec![];
+                    let number = index + 1;
+
+                    if index > 0 {
+                        row.push(InlineKeyboardButton::callback(format!("⬆ {number}"), CallbackData::MPH { index }));
+                    }
+                    row.push(InlineKeyboardButton::callback(format!("🔄 {number}"), CallbackData::RPH { index }));
+                    if count > 1 {
+                        row.push(InlineKeyboardButton::callback(format!("🗑 {number}"), CallbackData::DPH { index }));
+                    }
+
+                    row
+                })
+                .collect();
+
+            if count < max {
+                rows.push(vec![InlineKeyboardButton::callback(ADD_PHOTO, CallbackData::APH)]);
+            }
+            rows.push(vec![InlineKeyboardButton::callback(BACK, CallbackData::BPE)]);
+
+            InlineKeyboardMarkup::new(rows)
+        }
+    }
+
+    pub struct SkipVideo;
+
+    impl MakeKeyboard for SkipVideo {
+        fn keyboard() -> KeyboardMarkup {
+            KeyboardMarkup::new([[KeyboardButton::new(SKIP)]])
+                .resize_keyboard(true)
+                .one_time_keyboard(true)
+        }
+    }
+
+    pub struct RemoveVideo;
+
+    impl MakeKeyboardInline for RemoveVideo {
+        fn keyboard_inline() -> InlineKeyboardMarkup {
+            InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(REMOVE_VIDEO, CallbackData::RVD)]])
+        }
+    }
+
+    pub struct PhotosDone;
+
+    impl MakeKeyboard for PhotosDone {
+        fn keyboard() -> KeyboardMarkup {
+            KeyboardMarkup::new([[KeyboardButton::new(DONE)]])
+                .resize_keyboard(true)
+                .one_time_keyboard(true)
+        }
+    }
+
+    pub struct ConfirmDeletion;
+
+    impl ConfirmDeletion {
+        pub fn keyboard() -> InlineKeyboardMarkup {
+            InlineKeyboardMarkup::new([
+                [InlineKeyboardButton::callback(CONFIRM_DELETION, CallbackData::CDA)],
+                [InlineKeyboardButton::callback(CANCEL, CallbackData::CAD)],
+            ])
+        }
+    }
+
+    pub struct ChooseCity;
+
+    impl ChooseCity {
+        pub fn keyboard(candidates: &[profile::Location]) -> InlineKeyboardMarkup {
+            InlineKeyboardMarkup::new(candidates.iter().enumerate().map(|(index, location)| {
+                [InlineKeyboardButton::callback(location.displayed(), CallbackData::CTY { index })]
+            }))
+        }
+    }
+
+    pub struct ConfirmBroadcast;
+
+    impl ConfirmBroadcast {
+        pub fn keyboard() -> InlineKeyboardMarkup {
+            InlineKeyboardMarkup::new([
+                [InlineKeyboardButton::callback(CONFIRM_BROADCAST, CallbackData::BCS)],
+                [InlineKeyboardButton::callback(CANCEL, CallbackData::BCC)],
+            ])
+        }
+    }
+
     pub struct LookingAtProfiles;
 
     impl MakeKeyboard for LookingAtProfiles {
         fn keyboard() -> KeyboardMarkup {
-            KeyboardMarkup::new([[
-                KeyboardButton::new(LIKE),
-                KeyboardButton::new(DISLIKE),
-                KeyboardButton::new(MENU),
-            ]])
+            KeyboardMarkup::new([
+                vec![
+                    KeyboardButton::new(LIKE),
+                    KeyboardButton::new(LIKE_WITH_MESSAGE),
+                    KeyboardButton::new(DISLIKE),
+                    KeyboardButton::new(MENU),
+                ],
+                vec![KeyboardButton::new(REPORT), KeyboardButton::new(BLOCK)],
+            ])
+            .resize_keyboard(true)
+        }
+    }
+
+    pub struct LookingAtLikes;
+
+    impl MakeKeyboard for LookingAtLikes {
+        fn keyboard() -> KeyboardMarkup {
+            KeyboardMarkup::new([
+                vec![
+                    KeyboardButton::new(LIKE),
+                    KeyboardButton::new(DISLIKE),
+                    KeyboardButton::new(MENU),
+                ],
+                vec![KeyboardButton::new(REPORT), KeyboardButton::new(BLOCK)],
+            ])
             .resize_keyboard(true)
         }
     }
 
+    pub struct SelectReportReason;
+
+    impl SelectReportReason {
+        pub fn keyboard(user_id: MyUserId) -> InlineKeyboardMarkup {
+            let mut rows: Vec<Vec<InlineKeyboardButton>> = enum_iterator::all::<ReportReason>()
+                .map(|reason| {
+                    vec![InlineKeyboardButton::callback(
+                        reason.description(),
+                        CallbackData::RPR { user_id, reason },
+                    )]
+                })
+                .collect();
+
+            rows.push(vec![InlineKeyboardButton::callback(CANCEL, CallbackData::RPC)]);
+
+            InlineKeyboardMarkup::new(rows)
+        }
+    }
+
+    pub struct BlockMatch;
+
+    impl BlockMatch {
+        pub fn keyboard(user_id: MyUserId) -> InlineKeyboardMarkup {
+            InlineKeyboardMarkup::new([[
+                InlineKeyboardButton::callback(BLOCK, CallbackData::BLK { user_id }),
+            ]])
+        }
+    }
+
+    pub struct ModerateReport;
+
+    impl ModerateReport {
+        pub fn keyboard(user_id: MyUserId) -> InlineKeyboardMarkup {
+            InlineKeyboardMarkup::new([[
+                InlineKeyboardButton::callback(DISMISS, CallbackData::MDS { user_id }),
+                InlineKeyboardButton::callback(WARN, CallbackData::MWR { user_id }),
+                InlineKeyboardButton::callback(BAN, CallbackData::MBN { user_id }),
+            ]])
+        }
+    }
+
+    pub struct CancelLikeMessage;
+
+    impl MakeKeyboard for CancelLikeMessage {
+        fn keyboard() -> KeyboardMarkup {
+            KeyboardMarkup::new([[KeyboardButton::new(CANCEL)]])
+                .resize_keyboard(true)
+                .one_time_keyboard(true)
+        }
+    }
+
     pub struct EnterProfileEditingMode;
 
     impl EnterProfileEditingMode {
@@ -372,14 +802,26 @@ pub mod keyboard {
 
     pub struct Menu;
 
+    impl Menu {
+        /// The pause button turns into a reminder when the profile is hidden from search
+        pub fn keyboard_for(show_up_in_search: bool) -> KeyboardMarkup {
+            let visibility = if show_up_in_search { PAUSE } else { RESUME };
+
+            KeyboardMarkup::new([
+                vec![
+                    KeyboardButton::new(WATCH_PROFILES),
+                    KeyboardButton::new(MY_PROFILE),
+                    KeyboardButton::new(WHO_LIKES_ME),
+                ],
+                vec![KeyboardButton::new(SEARCH_SETTINGS), KeyboardButton::new(visibility)],
+            ])
+            .resize_keyboard(true)
+        }
+    }
+
     impl MakeKeyboard for Menu {
         fn keyboard() -> KeyboardMarkup {
-            KeyboardMarkup::new([[
-                KeyboardButton::new(WATCH_PROFILES),
-                KeyboardButton::new(MY_PROFILE),
-                KeyboardButton::new(WHO_LIKES_ME),
-            ]])
-            .resize_keyboard(true)
+            Self::keyboard_for(true)
         }
     }
 }
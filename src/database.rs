@@ -1,268 +1,450 @@
-use crate::profile::{Like, Location, Profile, View};
+use crate::broadcast::{Broadcast, BroadcastAudience};
+use crate::profile::{
+    AgeFilter, Block, CachedLocation, DistanceFilter, HearingLevel, Like, Location, Match, PhotoId, Profile, ProfileVideo,
+    Report, ReportStatus, Sex, View,
+};
 use crate::user::*;
-use futures::stream::TryStreamExt;
-use mongodb::bson::{doc, Document};
-use mongodb::options::ClientOptions;
+use async_trait::async_trait;
+use mongodb::bson::{Bson, Document};
+use std::str::FromStr;
 use std::sync::Arc;
-use crate::profile;
+use thiserror::Error;
+
+pub type DatabaseResult<T> = Result<T, DatabaseError>;
+
+/// Failure of the storage itself, as opposed to a record which doesn't exist
+#[derive(Debug, Error)]
+pub enum DatabaseError {
+    #[error("mongodb error: {error:?}")]
+    MongoDb {
+        #[from]
+        error: mongodb::error::Error,
+    },
+
+    #[error("sqlite error: {error:?}")]
+    Sqlite {
+        #[from]
+        error: sqlx::Error,
+    },
+
+    #[error("unable to serialize a record: {error:?}")]
+    Serialization {
+        #[from]
+        error: mongodb::bson::ser::Error,
+    },
+
+    #[error("unable to deserialize a record: {error:?}")]
+    Deserialization {
+        #[from]
+        error: mongodb::bson::de::Error,
+    },
+
+    #[error("the target storage already has {users} users, refusing to copy into it")]
+    TargetNotEmpty { users: u64 },
+}
 
-/// Performs all database operations
+/// Operations the bot needs from the place where users, profiles and their relations are kept.
 ///
-/// Auto-removing likes and views is not implemented here, it must be done
-/// in the database itself
+/// [crate::mongo_backend::MongoBackend] is the production one,
+/// [crate::memory_backend::MemoryBackend] keeps everything in memory
+#[async_trait]
+pub trait DatabaseBackend: Send + Sync {
+    async fn add_user(&self, user: &User) -> DatabaseResult<()>;
+
+    async fn add_profile(&self, profile: &Profile) -> DatabaseResult<()>;
+
+    async fn find_suggestion(
+        &self,
+        profile: &Profile,
+        viewed_profiles: &[View],
+        blocked_users: &[MyUserId],
+    ) -> DatabaseResult<Option<Profile>>;
+
+    async fn set_profile_name(&self, user_id: MyUserId, v: &str) -> DatabaseResult<()>;
+
+    async fn set_profile_age(&self, user_id: MyUserId, v: i64) -> DatabaseResult<()>;
+
+    async fn set_profile_location(&self, user_id: MyUserId, v: Location) -> DatabaseResult<()>;
+
+    async fn set_profile_hearing_level(&self, user_id: MyUserId, v: HearingLevel) -> DatabaseResult<()>;
+
+    async fn set_profile_description(&self, user_id: MyUserId, v: Option<&str>) -> DatabaseResult<()>;
+
+    async fn set_profile_video(&self, user_id: MyUserId, v: Option<ProfileVideo>) -> DatabaseResult<()>;
+
+    async fn set_profile_photos(&self, user_id: MyUserId, pics: &[PhotoId]) -> DatabaseResult<()>;
+
+    async fn set_profile_visibility(&self, user_id: MyUserId, show_up_in_search: bool) -> DatabaseResult<()>;
+
+    async fn set_search_age(&self, user_id: MyUserId, v: Option<AgeFilter>) -> DatabaseResult<()>;
+
+    async fn set_search_sex(&self, user_id: MyUserId, v: Option<Sex>) -> DatabaseResult<()>;
+
+    async fn set_search_hearing_level(&self, user_id: MyUserId, v: Option<Vec<HearingLevel>>) -> DatabaseResult<()>;
+
+    async fn set_search_distance(&self, user_id: MyUserId, v: Option<DistanceFilter>) -> DatabaseResult<()>;
+
+    async fn add_like(&self, like: Like) -> DatabaseResult<()>;
+
+    async fn add_match(&self, value: Match) -> DatabaseResult<()>;
+
+    async fn get_user_matches(&self, user_id: MyUserId) -> DatabaseResult<Vec<Match>>;
+
+    async fn add_view(&self, view: View) -> DatabaseResult<()>;
+
+    /// Returns all likes sent to the user, oldest first
+    async fn get_likes_to_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Like>>;
+
+    /// Returns all likes the user has sent, oldest first
+    async fn get_likes_from_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Like>>;
+
+    async fn find_like(
+        &self,
+        from: MyUserId,
+        to: MyUserId,
+    ) -> DatabaseResult<Option<Like>>;
+
+    async fn remove_like(&self, from: MyUserId, to: MyUserId) -> DatabaseResult<()>;
+
+    async fn get_user_views(&self, user_id: MyUserId) -> DatabaseResult<Vec<View>>;
+
+    /// Looks the user up by the username without `@`
+    async fn find_user_by_username(&self, username: &str) -> DatabaseResult<Option<User>>;
+
+    async fn count_users(&self) -> DatabaseResult<u64>;
+
+    async fn count_profiles(&self) -> DatabaseResult<u64>;
+
+    /// Counts likes given since the moment, including the ones which already became matches
+    async fn count_likes_since(&self, since: mongodb::bson::DateTime) -> DatabaseResult<u64>;
+
+    async fn count_matches_since(&self, since: mongodb::bson::DateTime) -> DatabaseResult<u64>;
+
+    async fn get_user(&self, id: MyUserId) -> DatabaseResult<Option<User>>;
+
+    async fn get_profile(&self, id: MyUserId) -> DatabaseResult<Option<Profile>>;
+
+    async fn add_report(&self, report: &Report) -> DatabaseResult<()>;
+
+    /// Number of distinct users with open reports on the user
+    async fn count_reporters(&self, user_id: MyUserId) -> DatabaseResult<u64>;
+
+    /// Closes all open reports on the user with the moderator's decision
+    async fn resolve_reports(&self, user_id: MyUserId, status: ReportStatus) -> DatabaseResult<()>;
+
+    async fn get_reports_from_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Report>>;
+
+    async fn add_block(&self, block: &Block) -> DatabaseResult<()>;
+
+    /// Returns _true_ if either of the users has blocked the other one
+    async fn is_blocked(&self, first: MyUserId, second: MyUserId) -> DatabaseResult<bool>;
+
+    /// Returns IDs of the users blocked by the user together with the ones who have blocked the user
+    async fn get_blocked_users(&self, user_id: MyUserId) -> DatabaseResult<Vec<MyUserId>>;
+
+    async fn get_blocks_from_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Block>>;
+
+    /// Stores the ban as it is, unless the user is already banned
+    async fn add_ban(&self, ban: &Ban) -> DatabaseResult<()>;
+
+    /// Bans the user and hides their profile from search
+    async fn ban_user(&self, user_id: MyUserId) -> DatabaseResult<()>;
+
+    async fn unban_user(&self, user_id: MyUserId) -> DatabaseResult<()>;
+
+    async fn count_broadcast_recipients(&self, audience: &BroadcastAudience) -> DatabaseResult<u64>;
+
+    async fn get_broadcast_recipients(&self, audience: &BroadcastAudience) -> DatabaseResult<Vec<MyUserId>>;
+
+    async fn add_broadcast(&self, broadcast: &Broadcast) -> DatabaseResult<()>;
+
+    async fn update_broadcast(&self, broadcast: &Broadcast) -> DatabaseResult<()>;
+
+    async fn is_banned(&self, user_id: MyUserId) -> DatabaseResult<bool>;
+
+    /// Replaces the cached location with the same key
+    async fn cache_location(&self, cached: &CachedLocation) -> DatabaseResult<()>;
+
+    /// Unexpired location cached under the normalized key, see [crate::maps::cache_key]
+    async fn get_cached_location(&self, key: &str) -> DatabaseResult<Option<CachedLocation>>;
+
+    /// Removes every document keyed by the user's id and records the deletion.
+    ///
+    /// Dialogue state is not touched here, it belongs to the dialogue storage.
+    /// Bans, blocks and reports against the user are kept so that deleting the account can't be used to lift a ban or a hold
+    async fn delete_user_data(&self, user_id: MyUserId) -> DatabaseResult<DeletionRecord>;
+
+    async fn add_deletion(&self, record: &DeletionRecord) -> DatabaseResult<()>;
+}
+
+/// Performs all database operations through the configured [DatabaseBackend]
 pub struct Database {
-    client: mongodb::Client,
+    backend: Arc<dyn DatabaseBackend>,
 }
 
 impl Database {
-    pub async fn init() -> Arc<Self> {
-        let mut options = ClientOptions::parse("mongodb://localhost:27017/deafbot")
-            .await
-            .unwrap();
+    pub fn new(backend: Arc<dyn DatabaseBackend>) -> Arc<Self> {
+        Arc::new(Self { backend })
+    }
 
-        options.app_name = Some(String::from("DeafBot"));
+    pub async fn add_user(&self, user: &User) -> DatabaseResult<()> {
+        self.backend.add_user(user).await
+    }
 
-        Arc::new(Self {
-            client: mongodb::Client::with_options(options).unwrap(),
-        })
+    pub async fn add_profile(&self, profile: &Profile) -> DatabaseResult<()> {
+        self.backend.add_profile(profile).await
     }
 
-    pub async fn add_user(&self, user: &User) {
-        log::trace!("new record of the user with id `{}` created", user.id());
-
-        self.users_collection()
-            .insert_one(user, None)
-            .await
-            .unwrap();
-    }
-
-    pub async fn add_profile(&self, profile: &Profile) {
-        log::trace!("new record of the profile with id `{}` created", profile.id());
-
-        self.profiles_collection()
-            .insert_one(profile, None)
-            .await
-            .unwrap();
-    }
-
-    pub async fn filter_profile(&self, filter: Document) -> Option<Profile> {
-        let mut cursor = match self.profiles_collection().find(filter, None).await {
-            Ok(curs) => curs,
-            Err(_) => return None,
-        };
-
-        cursor.try_next().await.map_or(None, |el| el)
-    }
-
-    pub async fn set_profile_name(&self, user_id: impl Into<MyUserId>, v: &str) {
-        self.profiles_collection()
-            .update_one(
-                doc! { "id": user_id.into() },
-                doc! { "$set": { "name": v } },
-                None
-            )
-            .await
-            .unwrap();
-    }
-
-    pub async fn set_profile_age(&self, user_id: impl Into<MyUserId>, v: i64) {
-        self.profiles_collection()
-            .update_one(
-                doc! { "id": user_id.into() },
-                doc! { "$set": { "age": v } },
-                None
-            )
-            .await
-            .unwrap();
-    }
-
-    pub async fn set_profile_location(&self, user_id: impl Into<MyUserId>, v: Location) {
-        self.profiles_collection()
-            .update_one(
-                doc! { "id": user_id.into() },
-                doc! { "$set": { "location": v } },
-                None
-            )
-            .await
-            .unwrap();
-    }
-
-    pub async fn set_profile_hearing_level(&self, user_id: impl Into<MyUserId>, v: profile::HearingLevel) {
-        self.profiles_collection()
-            .update_one(
-                doc! { "id": user_id.into() },
-                doc! { "$set": { "hearing_level": v } },
-                None
-            )
-            .await
-            .unwrap();
-    }
-
-    pub async fn set_profile_description(&self, user_id: impl Into<MyUserId>, v: impl Into<Option<&str>>) {
-        match v.into() {
-            Some(description) => {
-                self.profiles_collection()
-                    .update_one(
-                        doc! { "id": user_id.into() },
-                        doc! { "$set": { "description": description } },
-                        None
-                    )
-                    .await
-                    .unwrap();
-            }
-            None => {
-                self.profiles_collection()
-                    .update_one(
-                        doc! { "id": user_id.into() },
-                        doc! { "$unset": { "description": "" } },
-                        None
-                    )
-                    .await
-                    .unwrap();
-            }
-        }
+    pub async fn find_suggestion(
+        &self,
+        profile: &Profile,
+        viewed_profiles: &[View],
+        blocked_users: &[MyUserId],
+    ) -> DatabaseResult<Option<Profile>> {
+        self.backend.find_suggestion(profile, viewed_profiles, blocked_users).await
     }
 
-    pub async fn set_profile_picture(&self, user_id: impl Into<MyUserId>, pics: &[String]) {
-        self.profiles_collection()
-            .update_one(
-                doc! { "id": user_id.into() },
-                doc! { "$set": { "photos": pics } },
-                None
-            )
-            .await
-            .unwrap();
+    pub async fn set_profile_name(&self, user_id: impl Into<MyUserId>, v: &str) -> DatabaseResult<()> {
+        self.backend.set_profile_name(user_id.into(), v).await
     }
 
-    pub async fn add_like(&self, like: Like) {
-        self.likes_collection()
-            .insert_one(like, None)
-            .await
-            .unwrap();
+    pub async fn set_profile_age(&self, user_id: impl Into<MyUserId>, v: i64) -> DatabaseResult<()> {
+        self.backend.set_profile_age(user_id.into(), v).await
     }
 
-    pub async fn add_view(&self, view: View) {
-        self.views_collection()
-            .insert_one(view, None)
-            .await
-            .unwrap();
+    pub async fn set_profile_location(&self, user_id: impl Into<MyUserId>, v: Location) -> DatabaseResult<()> {
+        self.backend.set_profile_location(user_id.into(), v).await
     }
 
-    /// Returns [Like] where _from_ is ID of the user who liked the user, _to_ is the specified user_id
-    ///
-    /// Returns _None_ if there is no such record in the database with the specified user_id
-    pub async fn get_like_to_user(&self, user_id: impl Into<MyUserId>) -> Option<Like> {
-        let mut cursor = match self
-            .likes_collection()
-            .find(doc! { "to": user_id.into() }, None)
-            .await
-        {
-            Ok(curs) => curs,
-            Err(_) => return None,
-        };
+    pub async fn set_profile_hearing_level(&self, user_id: impl Into<MyUserId>, v: HearingLevel) -> DatabaseResult<()> {
+        self.backend.set_profile_hearing_level(user_id.into(), v).await
+    }
+
+    pub async fn set_profile_description(
+        &self,
+        user_id: impl Into<MyUserId>,
+        v: impl Into<Option<&str>>,
+    ) -> DatabaseResult<()> {
+        self.backend.set_profile_description(user_id.into(), v.into()).await
+    }
+
+    pub async fn set_profile_video(&self, user_id: impl Into<MyUserId>, v: Option<ProfileVideo>) -> DatabaseResult<()> {
+        self.backend.set_profile_video(user_id.into(), v).await
+    }
+
+    pub async fn set_profile_photos(&self, user_id: impl Into<MyUserId>, pics: &[PhotoId]) -> DatabaseResult<()> {
+        self.backend.set_profile_photos(user_id.into(), pics).await
+    }
+
+    pub async fn set_profile_visibility(&self, user_id: impl Into<MyUserId>, show_up_in_search: bool) -> DatabaseResult<()> {
+        self.backend.set_profile_visibility(user_id.into(), show_up_in_search).await
+    }
+
+    pub async fn set_search_age(&self, user_id: impl Into<MyUserId>, v: Option<AgeFilter>) -> DatabaseResult<()> {
+        self.backend.set_search_age(user_id.into(), v).await
+    }
 
-        cursor.try_next().await.map_or(None, |el| el)
+    pub async fn set_search_sex(&self, user_id: impl Into<MyUserId>, v: Option<Sex>) -> DatabaseResult<()> {
+        self.backend.set_search_sex(user_id.into(), v).await
     }
 
-    pub async fn find_like(
+    pub async fn set_search_hearing_level(
         &self,
-        from: impl Into<MyUserId>,
-        to: impl Into<MyUserId>,
-    ) -> Option<Like> {
-        self.likes_collection()
-            .find_one(
-                doc! {
-                    "$and": [
-                        { "from": from.into() },
-                        { "to": to.into() }
-                    ]
-                },
-                None,
-            )
-            .await
-            .unwrap()
-    }
-
-    pub async fn remove_like(&self, from: impl Into<MyUserId>, to: impl Into<MyUserId>) {
-        self.likes_collection()
-            .delete_one(
-                doc! {
-                    "$and": [
-                        { "from": from.into() },
-                        { "to": to.into() }
-                    ]
-                },
-                None,
-            )
-            .await
-            .unwrap();
-    }
-
-    pub async fn get_user_views(&self, user_id: impl Into<MyUserId>) -> Vec<View> {
-        let mut list: Vec<View> = vec![];
-        let mut cursor = self
-            .views_collection()
-            .find(doc! { "from": user_id.into() }, None)
-            .await
-            .unwrap();
-        while let Ok(Some(view)) = cursor.try_next().await {
-            list.push(view);
-        }
-        list
+        user_id: impl Into<MyUserId>,
+        v: Option<Vec<HearingLevel>>,
+    ) -> DatabaseResult<()> {
+        self.backend.set_search_hearing_level(user_id.into(), v).await
+    }
+
+    pub async fn set_search_distance(&self, user_id: impl Into<MyUserId>, v: Option<DistanceFilter>) -> DatabaseResult<()> {
+        self.backend.set_search_distance(user_id.into(), v).await
+    }
+
+    pub async fn add_like(&self, like: Like) -> DatabaseResult<()> {
+        self.backend.add_like(like).await
+    }
+
+    pub async fn add_match(&self, value: Match) -> DatabaseResult<()> {
+        self.backend.add_match(value).await
+    }
+
+    pub async fn get_user_matches(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<Vec<Match>> {
+        self.backend.get_user_matches(user_id.into()).await
+    }
+
+    pub async fn add_view(&self, view: View) -> DatabaseResult<()> {
+        self.backend.add_view(view).await
+    }
+
+    pub async fn get_likes_to_user(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<Vec<Like>> {
+        self.backend.get_likes_to_user(user_id.into()).await
+    }
+
+    pub async fn get_likes_from_user(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<Vec<Like>> {
+        self.backend.get_likes_from_user(user_id.into()).await
+    }
+
+    pub async fn find_like(&self, from: impl Into<MyUserId>, to: impl Into<MyUserId>) -> DatabaseResult<Option<Like>> {
+        self.backend.find_like(from.into(), to.into()).await
+    }
+
+    pub async fn remove_like(&self, from: impl Into<MyUserId>, to: impl Into<MyUserId>) -> DatabaseResult<()> {
+        self.backend.remove_like(from.into(), to.into()).await
+    }
+
+    pub async fn get_user_views(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<Vec<View>> {
+        self.backend.get_user_views(user_id.into()).await
+    }
+
+    pub async fn count_users(&self) -> DatabaseResult<u64> {
+        self.backend.count_users().await
+    }
+
+    pub async fn count_profiles(&self) -> DatabaseResult<u64> {
+        self.backend.count_profiles().await
+    }
+
+    pub async fn count_likes_since(&self, since: mongodb::bson::DateTime) -> DatabaseResult<u64> {
+        self.backend.count_likes_since(since).await
+    }
+
+    pub async fn count_matches_since(&self, since: mongodb::bson::DateTime) -> DatabaseResult<u64> {
+        self.backend.count_matches_since(since).await
+    }
+
+    pub async fn get_user(&self, id: impl Into<MyUserId>) -> DatabaseResult<Option<User>> {
+        self.backend.get_user(id.into()).await
+    }
+
+    pub async fn get_profile(&self, id: impl Into<MyUserId>) -> DatabaseResult<Option<Profile>> {
+        self.backend.get_profile(id.into()).await
+    }
+
+    pub async fn add_report(&self, report: &Report) -> DatabaseResult<()> {
+        self.backend.add_report(report).await
+    }
+
+    pub async fn count_reporters(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<u64> {
+        self.backend.count_reporters(user_id.into()).await
+    }
+
+    pub async fn resolve_reports(&self, user_id: impl Into<MyUserId>, status: ReportStatus) -> DatabaseResult<()> {
+        self.backend.resolve_reports(user_id.into(), status).await
     }
 
-    pub async fn get_user(&self, id: impl Into<MyUserId>) -> Option<User> {
-        let mut cursor = match self
-            .users_collection()
-            .find(doc! { "id": id.into() }, None)
-            .await
-        {
-            Ok(curs) => curs,
-            Err(_) => return None,
-        };
+    pub async fn get_reports_from_user(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<Vec<Report>> {
+        self.backend.get_reports_from_user(user_id.into()).await
+    }
+
+    pub async fn add_block(&self, block: &Block) -> DatabaseResult<()> {
+        self.backend.add_block(block).await
+    }
+
+    pub async fn is_blocked(&self, first: impl Into<MyUserId>, second: impl Into<MyUserId>) -> DatabaseResult<bool> {
+        self.backend.is_blocked(first.into(), second.into()).await
+    }
 
-        cursor.try_next().await.map_or(None, |el| el)
+    pub async fn get_blocked_users(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<Vec<MyUserId>> {
+        self.backend.get_blocked_users(user_id.into()).await
     }
 
-    pub async fn get_profile(&self, id: impl Into<MyUserId>) -> Option<Profile> {
-        let mut cursor = match self
-            .profiles_collection()
-            .find(doc! { "id": id.into() }, None)
-            .await
-        {
-            Ok(curs) => curs,
-            Err(_) => return None,
-        };
+    pub async fn get_blocks_from_user(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<Vec<Block>> {
+        self.backend.get_blocks_from_user(user_id.into()).await
+    }
+
+    pub async fn ban_user(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<()> {
+        self.backend.ban_user(user_id.into()).await
+    }
+
+    pub async fn unban_user(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<()> {
+        self.backend.unban_user(user_id.into()).await
+    }
+
+    pub async fn count_broadcast_recipients(&self, audience: &BroadcastAudience) -> DatabaseResult<u64> {
+        self.backend.count_broadcast_recipients(audience).await
+    }
+
+    pub async fn get_broadcast_recipients(&self, audience: &BroadcastAudience) -> DatabaseResult<Vec<MyUserId>> {
+        self.backend.get_broadcast_recipients(audience).await
+    }
 
-        cursor.try_next().await.map_or(None, |el| el)
+    pub async fn add_broadcast(&self, broadcast: &Broadcast) -> DatabaseResult<()> {
+        self.backend.add_broadcast(broadcast).await
     }
 
-    #[inline]
-    fn users_collection(&self) -> mongodb::Collection<User> {
-        log::trace!("users collection access requested");
-        self.local().collection("users")
+    pub async fn update_broadcast(&self, broadcast: &Broadcast) -> DatabaseResult<()> {
+        self.backend.update_broadcast(broadcast).await
     }
 
-    #[inline]
-    fn profiles_collection(&self) -> mongodb::Collection<Profile> {
-        log::trace!("profiles collection access requested");
-        self.local().collection("profiles")
+    pub async fn is_banned(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<bool> {
+        self.backend.is_banned(user_id.into()).await
     }
 
-    #[inline]
-    fn likes_collection(&self) -> mongodb::Collection<Like> {
-        log::trace!("likes collection access requested");
-        self.local().collection("likes")
+    pub async fn delete_user_data(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<DeletionRecord> {
+        self.backend.delete_user_data(user_id.into()).await
     }
 
-    #[inline]
-    fn views_collection(&self) -> mongodb::Collection<View> {
-        log::trace!("views collection access requested");
-        self.local().collection("views")
+    pub async fn cache_location(&self, cached: &CachedLocation) -> DatabaseResult<()> {
+        self.backend.cache_location(cached).await
     }
 
-    #[inline]
-    fn local(&self) -> mongodb::Database {
-        self.client.database("deafbot")
+    pub async fn get_cached_location(&self, key: &str) -> DatabaseResult<Option<CachedLocation>> {
+        self.backend.get_cached_location(key).await
+    }
+
+    /// Looks the user up by a numeric ID or by a username with or without `@`
+    pub async fn find_user_id(&self, target: &str) -> DatabaseResult<Option<MyUserId>> {
+        if let Ok(id) = MyUserId::from_str(target) {
+            return Ok(Some(id));
+        }
+
+        Ok(self.backend
+            .find_user_by_username(target.trim_start_matches('@'))
+            .await?
+            .map(|user| *user.id()))
+    }
+
+    /// Collects every record about the user into one document
+    pub async fn export_user_data(&self, user_id: impl Into<MyUserId>) -> DatabaseResult<UserDataExport> {
+        let user_id = user_id.into();
+
+        Ok(UserDataExport {
+            user: self.get_user(user_id).await?,
+            profile: self.get_profile(user_id).await?,
+            likes_sent: self.get_likes_from_user(user_id).await?,
+            likes_received: self.get_likes_to_user(user_id).await?,
+            views: self.get_user_views(user_id).await?,
+            reports_sent: self.get_reports_from_user(user_id).await?,
+            blocks_sent: self.get_blocks_from_user(user_id).await?,
+            matches: self.get_user_matches(user_id).await?,
+        })
+    }
+}
+
+/// Applies the same `$set`, or `$unset` if the value is _None_, as [crate::mongo_backend::MongoBackend]
+/// does to the dotted path of the profile. Used by backends which store whole profiles
+pub(crate) fn update_profile(profile: &Profile, path: &str, value: Option<Bson>) -> DatabaseResult<Profile> {
+    let mut document = mongodb::bson::to_document(profile)?;
+    set_path(&mut document, path, value);
+    Ok(mongodb::bson::from_document(document)?)
+}
+
+fn set_path(document: &mut Document, path: &str, value: Option<Bson>) {
+    match path.split_once('.') {
+        Some((head, rest)) => {
+            if !matches!(document.get(head), Some(Bson::Document(_))) {
+                document.insert(head, Document::new());
+            }
+
+            set_path(document.get_document_mut(head).unwrap(), rest, value);
+        }
+        None => match value {
+            Some(value) => {
+                document.insert(path, value);
+            }
+            None => {
+                document.remove(path);
+            }
+        },
     }
 }
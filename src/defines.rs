@@ -1,7 +1,7 @@
 use crate::state::State;
 use std::error::Error;
-use teloxide::dispatching::dialogue::InMemStorage;
+use teloxide::dispatching::dialogue::ErasedStorage;
 use teloxide::prelude::Dialogue;
 
 pub type TeloxideResult = Result<(), Box<dyn Error + Send + Sync>>;
-pub type BotDialogue = Dialogue<State, InMemStorage<State>>;
\ No newline at end of file
+pub type BotDialogue = Dialogue<State, ErasedStorage<State>>;
@@ -0,0 +1,112 @@
+use futures::future::BoxFuture;
+use mongodb::bson::{doc, Document};
+use mongodb::options::UpdateOptions;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::Arc;
+use teloxide::dispatching::dialogue::Storage;
+use teloxide::types::ChatId;
+use thiserror::Error;
+
+/// A persistent dialogue storage based on the `dialogues` collection.
+///
+/// Every dialogue is stored as a separate document keyed by `chat_id`
+pub struct MongoStorage {
+    collection: mongodb::Collection<Document>,
+}
+
+impl MongoStorage {
+    pub fn new(collection: mongodb::Collection<Document>) -> Arc<Self> {
+        Arc::new(Self { collection })
+    }
+}
+
+impl<D> Storage<D> for MongoStorage
+where
+    D: Send + Serialize + DeserializeOwned + 'static,
+{
+    type Error = MongoStorageError;
+
+    fn remove_dialogue(
+        self: Arc<Self>,
+        ChatId(chat_id): ChatId,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        Box::pin(async move {
+            let result = self
+                .collection
+                .delete_one(doc! { "chat_id": chat_id }, None)
+                .await?;
+
+            if result.deleted_count == 0 {
+                return Err(MongoStorageError::DialogueNotFound);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn update_dialogue(
+        self: Arc<Self>,
+        ChatId(chat_id): ChatId,
+        dialogue: D,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        Box::pin(async move {
+            let dialogue = mongodb::bson::to_bson(&dialogue)?;
+
+            self.collection
+                .update_one(
+                    doc! { "chat_id": chat_id },
+                    doc! { "$set": { "dialogue": dialogue } },
+                    UpdateOptions::builder().upsert(true).build(),
+                )
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_dialogue(
+        self: Arc<Self>,
+        ChatId(chat_id): ChatId,
+    ) -> BoxFuture<'static, Result<Option<D>, Self::Error>> {
+        Box::pin(async move {
+            let document = match self
+                .collection
+                .find_one(doc! { "chat_id": chat_id }, None)
+                .await?
+            {
+                Some(document) => document,
+                None => return Ok(None),
+            };
+
+            match document.get("dialogue") {
+                Some(dialogue) => Ok(Some(mongodb::bson::from_bson(dialogue.clone())?)),
+                None => Ok(None),
+            }
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum MongoStorageError {
+    #[error("mongodb error: {error:?}")]
+    MongoError {
+        #[from]
+        error: mongodb::error::Error,
+    },
+
+    #[error("dialogue serialization error: {error:?}")]
+    SerializationError {
+        #[from]
+        error: mongodb::bson::ser::Error,
+    },
+
+    #[error("dialogue deserialization error: {error:?}")]
+    DeserializationError {
+        #[from]
+        error: mongodb::bson::de::Error,
+    },
+
+    #[error("dialogue not found")]
+    DialogueNotFound,
+}
@@ -0,0 +1,246 @@
+use crate::maps::{normalize_city_name as normalize, FetchingError, GeocodedCity, Geocoder};
+use crate::profile::Coordinates;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Largest Russian cities, used when no gazetteer file is configured
+const BUNDLED_CITIES: &str = include_str!("../assets/cities.json");
+
+/// Coordinates farther than this from every known city are left to the next geocoder
+const MAX_REVERSE_DISTANCE_METERS: f64 = 30_000.0;
+
+#[derive(Deserialize)]
+struct City {
+    name: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    region: String,
+    latitude: f64,
+    longitude: f64,
+    #[serde(default)]
+    population: u64,
+}
+
+/// [City] with its names prepared for matching
+struct Entry {
+    city: City,
+    names: Vec<String>,
+    region: String,
+    coordinates: Coordinates,
+}
+
+/// Offline geocoder over a list of cities, doesn't make any requests.
+///
+/// It knows only a part of the cities, so a city it can't find is looked up by the next geocoder
+pub struct GazetteerGeocoder {
+    entries: Vec<Entry>,
+    fuzzy: bool,
+}
+
+impl GazetteerGeocoder {
+    /// Loads the gazetteer from a JSON file, or the bundled one if `path` is `None`.
+    ///
+    /// Misspelled names are matched only if `fuzzy`, which is for a gazetteer with no online geocoder after it:
+    /// otherwise a town missing from the list, like Орск, would be taken for a known city, like Омск
+    pub fn open(path: Option<&str>, fuzzy: bool) -> Arc<Self> {
+        let json = match path {
+            Some(path) => std::fs::read_to_string(path)
+                .unwrap_or_else(|error| panic!("Unable to read gazetteer file `{path}`: {error}")),
+            None => BUNDLED_CITIES.to_owned(),
+        };
+
+        let cities: Vec<City> = serde_json::from_str(&json)
+            .expect("Unable to parse gazetteer file");
+
+        let entries = cities.into_iter()
+            .map(|city| Entry {
+                names: std::iter::once(&city.name)
+                    .chain(&city.aliases)
+                    .map(|name| normalize(name))
+                    .collect(),
+                region: normalize(&city.region),
+                coordinates: Coordinates::new(city.longitude, city.latitude),
+                city,
+            })
+            .collect();
+
+        Arc::new(Self { entries, fuzzy })
+    }
+
+    /// Exact matches on any of the names first, then the closest spellings if [GazetteerGeocoder::fuzzy],
+    /// the most populated first. `"Name, Region"` input prefers the cities from that region
+    fn find(&self, input: &str) -> Vec<&Entry> {
+        let (name, region) = match input.split_once(',') {
+            Some((name, region)) => (normalize(name), Some(normalize(region))),
+            None => (normalize(input), None),
+        };
+
+        if name.is_empty() {
+            return Vec::new();
+        }
+
+        let max_distance = match name.chars().count() {
+            _ if !self.fuzzy => 0,
+            0..=3 => 0,
+            4..=6 => 1,
+            _ => 2,
+        };
+
+        let mut found: Vec<_> = self.entries.iter()
+            .filter_map(|entry| {
+                let distance = entry.names.iter()
+                    .map(|candidate| levenshtein(&name, candidate))
+                    .min()?;
+
+                let other_region = region.as_ref()
+                    .is_some_and(|region| !entry.region.contains(region.as_str()));
+
+                (distance <= max_distance).then_some((entry, (other_region, distance)))
+            })
+            .collect();
+
+        let Some(best) = found.iter().map(|(_, rank)| *rank).min() else {
+            return Vec::new();
+        };
+
+        found.retain(|(_, rank)| *rank == best);
+        found.sort_by_key(|(entry, _)| std::cmp::Reverse(entry.city.population));
+
+        found.into_iter().map(|(entry, _)| entry).collect()
+    }
+
+    fn city(entry: &Entry) -> GeocodedCity {
+        GeocodedCity {
+            name: entry.city.name.clone(),
+            region: Some(entry.city.region.clone()).filter(|region| !region.is_empty()),
+            coordinates: Some(entry.coordinates.clone()),
+        }
+    }
+}
+
+#[async_trait]
+impl Geocoder for GazetteerGeocoder {
+    fn name(&self) -> &'static str {
+        "gazetteer"
+    }
+
+    fn is_exhaustive(&self) -> bool {
+        false
+    }
+
+    /// Only names without a region, `"Name, Region"` may point to another city with the same name
+    fn canonical_name(&self, name: &str) -> Option<String> {
+        if name.contains(',') {
+            return None;
+        }
+
+        let name = normalize(name);
+        let mut found = self.entries.iter().filter(|entry| entry.names.contains(&name));
+
+        match (found.next(), found.next()) {
+            (Some(entry), None) => Some(entry.city.name.clone()),
+            _ => None,
+        }
+    }
+
+    async fn cities_by_name(&self, name: &str) -> Result<Vec<GeocodedCity>, FetchingError> {
+        let found = self.find(name);
+
+        if found.is_empty() {
+            return Err(FetchingError::CityNotFound { name: name.to_owned() });
+        }
+
+        Ok(found.into_iter().map(Self::city).collect())
+    }
+
+    async fn city_by_coordinates(&self, coordinates: &Coordinates) -> Result<GeocodedCity, FetchingError> {
+        let nearest = self.entries.iter()
+            .map(|entry| (entry, entry.coordinates.distance_to(coordinates)))
+            .filter(|(_, distance)| *distance <= MAX_REVERSE_DISTANCE_METERS)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        match nearest {
+            Some((entry, _)) => Ok(Self::city(entry)),
+            None => Err(FetchingError::CityNotFound {
+                name: format!("{},{}", coordinates.longitude(), coordinates.latitude()),
+            }),
+        }
+    }
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn find(gazetteer: &GazetteerGeocoder, name: &str) -> Result<Vec<String>, FetchingError> {
+        let cities = gazetteer.cities_by_name(name).await?;
+        Ok(cities.into_iter().map(|city| city.name).collect())
+    }
+
+    #[tokio::test]
+    async fn finds_names_and_aliases() {
+        let gazetteer = GazetteerGeocoder::open(None, false);
+
+        assert_eq!(find(&gazetteer, "г. Москва").await.unwrap(), ["Москва"]);
+        assert_eq!(find(&gazetteer, "Moscow").await.unwrap(), ["Москва"]);
+        assert_eq!(find(&gazetteer, "питер").await.unwrap(), ["Санкт-Петербург"]);
+    }
+
+    #[tokio::test]
+    async fn leaves_unknown_towns_to_the_next_geocoder() {
+        let gazetteer = GazetteerGeocoder::open(None, false);
+
+        for name in ["Орск", "Кировск", "Масква"] {
+            let error = find(&gazetteer, name).await.unwrap_err();
+            assert!(matches!(error, FetchingError::CityNotFound { .. }), "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn matches_misspellings_when_fuzzy() {
+        let gazetteer = GazetteerGeocoder::open(None, true);
+
+        assert_eq!(find(&gazetteer, "Масква").await.unwrap(), ["Москва"]);
+    }
+
+    #[test]
+    fn resolves_aliases_to_the_main_name() {
+        let gazetteer = GazetteerGeocoder::open(None, false);
+
+        assert_eq!(gazetteer.canonical_name("Moscow").as_deref(), Some("Москва"));
+        assert_eq!(gazetteer.canonical_name("г. Москва").as_deref(), Some("Москва"));
+        assert_eq!(gazetteer.canonical_name("Орск"), None);
+        assert_eq!(gazetteer.canonical_name("Moscow, Idaho"), None);
+    }
+
+    #[tokio::test]
+    async fn reverse_geocodes_into_the_city_centre() {
+        let gazetteer = GazetteerGeocoder::open(None, false);
+        let near_kremlin = Coordinates::new(37.62, 55.76);
+
+        let city = gazetteer.city_by_coordinates(&near_kremlin).await.unwrap();
+
+        assert_eq!(city.name, "Москва");
+        assert_ne!(city.coordinates, Some(near_kremlin));
+    }
+}
@@ -1,17 +1,27 @@
-#![feature(async_closure)]
-
 mod application;
+mod broadcast;
 mod callback;
 mod commands;
 mod common;
 mod database;
 mod defines;
+mod dialogue_storage;
 mod error;
+mod gazetteer_geocoder;
 mod maps;
+mod memory_backend;
+mod migrations;
+mod mongo_backend;
+mod nominatim_geocoder;
 mod perform;
 mod profile;
+mod resilient_geocoder;
+mod sqlite_backend;
 mod state;
+#[cfg(test)]
+mod stub_server;
 mod user;
+mod yandex_geocoder;
 
 #[tokio::main]
 async fn main() {
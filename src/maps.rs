@@ -1,128 +1,270 @@
-use crate::profile::Location;
-
-use futures::TryStreamExt;
-use mongodb::bson::doc;
-use mongodb::options::ClientOptions;
+use crate::database::{Database, DatabaseError, DatabaseResult};
+use crate::profile::{CachedLocation, Coordinates, Location};
 
+use async_trait::async_trait;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
+/// Turns what the user typed, or where they are, into a city
+#[async_trait]
+pub trait Geocoder: Send + Sync {
+    /// Shown in logs when the provider fails
+    fn name(&self) -> &'static str;
+
+    /// Whether [FetchingError::CityNotFound] means there is no such city at all.
+    /// Otherwise the next geocoder is asked
+    fn is_exhaustive(&self) -> bool {
+        true
+    }
+
+    /// Main name of the only city known by this name or alias, e.g. `Москва` for `Moscow`
+    fn canonical_name(&self, _name: &str) -> Option<String> {
+        None
+    }
+
+    /// Finds the cities with this name, best match first.
+    /// Returns [FetchingError::CityNotFound] if there is no such city
+    async fn cities_by_name(&self, name: &str) -> Result<Vec<GeocodedCity>, FetchingError>;
+
+    /// Finds the city nearest to the coordinates
+    async fn city_by_coordinates(&self, coordinates: &Coordinates) -> Result<GeocodedCity, FetchingError>;
+}
+
+/// City found by a [Geocoder]
+#[derive(Debug, Clone)]
+pub struct GeocodedCity {
+    pub name: String,
+    pub region: Option<String>,
+    pub coordinates: Option<Coordinates>,
+}
+
+impl GeocodedCity {
+    /// `"City, Region"`, or just the city when the region is the city itself
+    pub fn label(&self) -> String {
+        match &self.region {
+            Some(region) if *region != self.name => format!("{}, {region}", self.name),
+            _ => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum GeocodeRequest<'a> {
+    Name(&'a str),
+    Coordinates(&'a Coordinates),
+}
+
+impl Display for GeocodeRequest<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "{name}"),
+            Self::Coordinates(coordinates) => write!(f, "{},{}", coordinates.longitude(), coordinates.latitude()),
+        }
+    }
+}
+
 /// Performs all geocoding operations.
 ///
-/// Using Yandex Maps API
+/// Geocoders are asked in the configured order, the next one is used only when the previous one fails
+/// or doesn't know the city. A city which doesn't exist is not a failure, so it is never looked up twice.
+/// Results are cached under [cache_key], cities which don't exist are cached too but for a shorter time
 pub struct Maps {
-    client: mongodb::Client,
-    api_key: String,
+    database: Arc<Database>,
+    geocoders: Vec<Arc<dyn Geocoder>>,
+    cache_ttl: Duration,
+    not_found_ttl: Duration,
+    hits: AtomicU64,
+    not_found_hits: AtomicU64,
+    misses: AtomicU64,
 }
 
-impl Maps {
-    pub async fn init(api_key: String) -> Arc<Self> {
-        let options = ClientOptions::parse("mongodb://localhost:27017/deafbot")
-            .await
-            .unwrap();
+/// Location cache counters since the start
+#[derive(Debug, Clone, Copy)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub not_found_hits: u64,
+    pub misses: u64,
+}
 
+impl Maps {
+    pub fn new(
+        database: Arc<Database>,
+        geocoders: Vec<Arc<dyn Geocoder>>,
+        cache_ttl: Duration,
+        not_found_ttl: Duration,
+    ) -> Arc<Self> {
         Arc::new(Self {
-            client: mongodb::Client::with_options(options).unwrap(),
-            api_key,
+            database,
+            geocoders,
+            cache_ttl,
+            not_found_ttl,
+            hits: AtomicU64::new(0),
+            not_found_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
         })
     }
 
-    /// Gets location from cache or fetches it using Yandex Maps API
-    pub async fn get_actual_city(&self, input: &str) -> Result<Location, FetchingError> {
-        if let Some(location) = self.get_cached_location(input).await {
-            return Ok(location);
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            not_found_hits: self.not_found_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
         }
+    }
 
-        let geocode_json = self.fetch_geocode(input).await?;
+    /// Gets location from cache or fetches it from the geocoders.
+    ///
+    /// Returns several locations when the name is ambiguous, e.g. there are towns with the same name
+    /// in different regions. Such locations are named `"City, Region"` so people from different regions
+    /// don't end up in the same city, and they are not cached because the user has to choose one of them
+    pub async fn find_cities(&self, input: &str) -> Result<Vec<Location>, FetchingError> {
+        let input = input.trim();
+        let key = self.cache_key(input);
 
-        let actual_city = match Self::city_name_from_geocode_json(geocode_json) {
-            Some(city) => city,
-            None => return Err(FetchingError::CityNotFound { name: input.to_owned() })
-        };
+        if let Some(cached) = self.database.get_cached_location(&key).await? {
+            return match cached.location() {
+                Some(location) => {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    Ok(vec![Location::new(input, location.actual().clone(), location.coordinates().clone())])
+                }
+                None => {
+                    self.not_found_hits.fetch_add(1, Ordering::Relaxed);
+                    Err(FetchingError::CityNotFound { name: input.to_owned() })
+                }
+            };
+        }
 
-        let location = Location::new(input.to_owned(), actual_city, None);
+        self.misses.fetch_add(1, Ordering::Relaxed);
 
-        self.cache_location(&location).await;
+        let mut cities = match self.geocode(GeocodeRequest::Name(input)).await {
+            Err(error @ FetchingError::CityNotFound { .. }) => {
+                self.cache_location(key, None, self.not_found_ttl).await?;
+                return Err(error);
+            }
+            result => result?,
+        };
 
-        Ok(location)
-    }
+        let mut labels = Vec::with_capacity(cities.len());
+        cities.retain(|city| {
+            let label = city.label();
+            let is_new = !labels.contains(&label);
+            labels.push(label);
+            is_new
+        });
 
-    async fn fetch_geocode(&self, geocode: &str) -> Result<serde_json::Value, FetchingError> {
-        let payload = format!(
-            "https://geocode-maps.yandex.ru/1.x\
-            ?apikey={}\
-            &format=json\
-            &results=1\
-            &geocode={geocode}",
-            self.api_key
-        );
+        if let [city] = cities.as_slice() {
+            let location = Location::new(input, city.name.clone(), city.coordinates.clone());
 
-        let response = match reqwest::get(payload).await {
-            Ok(response) => response,
-            Err(error) => return Err(FetchingError::ReqwestError { error }),
-        };
+            self.cache_location(key, Some(location.clone()), self.cache_ttl).await?;
 
-        if !response.status().is_success() {
-            return Err(FetchingError::UnsuccessfulRequest {
-                response_code: response.status().as_u16(),
-            });
+            return Ok(vec![location]);
         }
 
-        match response.json::<serde_json::Value>().await {
-            Ok(data) => Ok(data),
-            Err(_) => Err(FetchingError::JsonNotFound),
-        }
+        Ok(cities.into_iter()
+            .map(|city| {
+                let label = city.label();
+                Location::new(label.clone(), label, city.coordinates)
+            })
+            .collect())
     }
 
-    async fn cache_location(&self, location: &Location) {
-        self.location_cache_collection()
-            .insert_one(location, None)
-            .await
-            .unwrap();
-    }
+    /// Reverse geocodes the coordinates into the nearest city and caches it by the city name.
+    ///
+    /// Only the city centre is cached, the returned location keeps the user's own coordinates for their profile
+    pub async fn get_city_by_coordinates(&self, coordinates: Coordinates) -> Result<Location, FetchingError> {
+        let city = self.geocode(GeocodeRequest::Coordinates(&coordinates)).await?
+            .swap_remove(0);
+        let centre = Location::new(city.name.clone(), city.name.clone(), city.coordinates);
 
-    /// Tries to get location from `location_cache` collection. Returns `None` if not found.
-    async fn get_cached_location(&self, location: &str) -> Option<Location> {
-        let mut cursor = match self
-            .location_cache_collection()
-            .find(doc! { "displayed": location }, None)
-            .await
-        {
-            Ok(curs) => curs,
-            Err(_) => return None,
-        };
+        self.cache_location(self.cache_key(centre.displayed()), Some(centre), self.cache_ttl).await?;
 
-        cursor.try_next().await.map_or(None, |cl| cl)
+        Ok(Location::new(city.name.clone(), city.name, Some(coordinates)))
     }
 
-    /// Tries to get city name from a geocode.
-    fn city_name_from_geocode_json(geocode_json: serde_json::Value) -> Option<String> {
-        let intersection = &geocode_json["response"]["GeoObjectCollection"]["featureMember"][0]
-            ["GeoObject"]["metaDataProperty"]["GeocoderMetaData"]["AddressDetails"]["Country"]
-            ["AdministrativeArea"];
+    /// Asks the geocoders one by one until one of them answers
+    async fn geocode(&self, request: GeocodeRequest<'_>) -> Result<Vec<GeocodedCity>, FetchingError> {
+        let mut last_error = FetchingError::NoGeocoders;
 
-        if let Some(city) =
-            intersection["SubAdministrativeArea"]["Locality"]["LocalityName"].as_str()
-        {
-            return Some(city.to_owned());
-        }
+        for geocoder in &self.geocoders {
+            let result = match request {
+                GeocodeRequest::Name(name) => geocoder.cities_by_name(name).await,
+                GeocodeRequest::Coordinates(coordinates) => geocoder.city_by_coordinates(coordinates).await
+                    .map(|city| vec![city]),
+            };
 
-        if let Some(city) = intersection["Locality"]["LocalityName"].as_str() {
-            return Some(city.to_owned());
+            match result {
+                Ok(cities) if !cities.is_empty() => return Ok(cities),
+                Ok(_) => last_error = FetchingError::CityNotFound { name: request.to_string() },
+                Err(error @ FetchingError::CityNotFound { .. }) if geocoder.is_exhaustive() => return Err(error),
+                Err(error @ FetchingError::CityNotFound { .. }) => last_error = error,
+                Err(error) => {
+                    log::warn!("geocoder `{}` failed: {error}", geocoder.name());
+                    last_error = error;
+                }
+            }
         }
 
-        intersection["AdministrativeAreaName"].as_str()
-            .map(|el| el.to_owned())
+        Err(last_error)
+    }
+
+    /// [cache_key] of the main name if a geocoder knows the input as an alias, so `Moscow` shares the entry of `Москва`
+    fn cache_key(&self, input: &str) -> String {
+        let name = self.geocoders.iter().find_map(|geocoder| geocoder.canonical_name(input));
+        cache_key(name.as_deref().unwrap_or(input))
     }
 
-    #[inline]
-    fn location_cache_collection(&self) -> mongodb::Collection<Location> {
-        self.local().collection("location_cache")
+    async fn cache_location(&self, key: String, location: Option<Location>, ttl: Duration) -> DatabaseResult<()> {
+        self.database.cache_location(&CachedLocation::new(key, location, ttl)).await
+    }
+}
+
+/// Lowercase, `ё` as `е`, no `г.` prefix, hyphens and repeated spaces as a single space
+pub fn normalize_city_name(input: &str) -> String {
+    let lowercase = input.to_lowercase().replace('ё', "е");
+
+    let words: Vec<_> = lowercase
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '.')
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    match words.as_slice() {
+        ["г" | "город", rest @ ..] => rest.join(" "),
+        words => words.join(" "),
+    }
+}
+
+/// Location cache key, the same for `"Москва"`, `" г. москва "` and `"Moskva"`.
+///
+/// The normalized name is transliterated into latin letters. Other names of the city, like `"Moscow"`,
+/// get their own key, [Maps] resolves the aliases it knows before making the key
+pub fn cache_key(input: &str) -> String {
+    normalize_city_name(input)
+        .chars()
+        .map(|c| match c {
+            'а' => "a", 'б' => "b", 'в' => "v", 'г' => "g", 'д' => "d", 'е' => "e", 'ж' => "zh",
+            'з' => "z", 'и' => "i", 'й' => "y", 'к' => "k", 'л' => "l", 'м' => "m", 'н' => "n",
+            'о' => "o", 'п' => "p", 'р' => "r", 'с' => "s", 'т' => "t", 'у' => "u", 'ф' => "f",
+            'х' => "kh", 'ц' => "ts", 'ч' => "ch", 'ш' => "sh", 'щ' => "shch", 'ъ' | 'ь' => "",
+            'ы' => "y", 'э' => "e", 'ю' => "yu", 'я' => "ya",
+            _ => return c.to_string(),
+        }.to_owned())
+        .collect()
+}
+
+/// Reads the JSON body of a successful response
+pub(crate) async fn response_json(response: reqwest::Response) -> Result<serde_json::Value, FetchingError> {
+    if !response.status().is_success() {
+        return Err(FetchingError::UnsuccessfulRequest {
+            response_code: response.status().as_u16(),
+        });
     }
 
-    #[inline]
-    fn local(&self) -> mongodb::Database {
-        self.client.database("deafbot")
+    match response.json::<serde_json::Value>().await {
+        Ok(data) => Ok(data),
+        Err(_) => Err(FetchingError::JsonNotFound),
     }
 }
 
@@ -141,5 +283,134 @@ pub enum FetchingError {
     JsonNotFound,
 
     #[error("city `{name}` doesn't exist")]
-    CityNotFound { name: String }
+    CityNotFound { name: String },
+
+    #[error("no geocoders configured")]
+    NoGeocoders,
+
+    #[error("geocoder `{geocoder}` is not asked after repeated failures")]
+    GeocoderUnavailable { geocoder: &'static str },
+
+    #[error("database error: {error:?}")]
+    Database {
+        #[from]
+        error: DatabaseError,
+    },
+}
+
+impl FetchingError {
+    /// Whether the same request may succeed if it's sent again a bit later
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::ReqwestError { error } => error.is_timeout() || error.is_connect() || error.is_request(),
+            Self::UnsuccessfulRequest { response_code } => *response_code == 429 || *response_code >= 500,
+            _ => false,
+        }
+    }
+}
+
+/// HTTP client for the online geocoders, a stuck provider must not keep the user waiting
+pub fn http_client_builder(timeout: Duration) -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .connect_timeout(timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gazetteer_geocoder::GazetteerGeocoder;
+    use crate::memory_backend::MemoryBackend;
+    use crate::nominatim_geocoder::NominatimGeocoder;
+    use crate::stub_server::StubServer;
+    use crate::yandex_geocoder::YandexGeocoder;
+
+    const ORSK: &str = r#"[{"place_id":1,"lat":"51.2293","lon":"58.4752","category":"place","type":"city","name":"Орск","address":{"city":"Орск","state":"Оренбургская область","country":"Россия","country_code":"ru"}}]"#;
+
+    const KHIMKI: &str = r#"{"place_id":2,"lat":"55.8888","lon":"37.4303","category":"boundary","type":"administrative","name":"Химки","address":{"city":"Химки","state":"Московская область","country":"Россия","country_code":"ru"}}"#;
+
+    const NOTHING: &str = r#"{"response":{"GeoObjectCollection":{"featureMember":[]}}}"#;
+
+    /// Yandex and Nominatim answering with the recorded responses, after the gazetteer if it's asked for
+    async fn maps(
+        gazetteer: bool,
+        yandex: Vec<(u16, &'static str)>,
+        nominatim: Vec<(u16, &'static str)>,
+    ) -> (Arc<Maps>, StubServer, StubServer) {
+        let (yandex, nominatim) = (StubServer::start(yandex).await, StubServer::start(nominatim).await);
+        let timeout = Duration::from_secs(5);
+
+        let mut geocoders: Vec<Arc<dyn Geocoder>> = vec![
+            YandexGeocoder::new(yandex.url.clone(), "key".to_owned(), timeout),
+            NominatimGeocoder::new(nominatim.url.clone(), "test".to_owned(), timeout),
+        ];
+        if gazetteer {
+            geocoders.insert(0, GazetteerGeocoder::open(None, false));
+        }
+
+        let maps = Maps::new(
+            Database::new(MemoryBackend::new()),
+            geocoders,
+            Duration::from_secs(3600),
+            Duration::from_secs(60),
+        );
+
+        (maps, yandex, nominatim)
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_next_geocoder_on_errors() {
+        let (maps, yandex, nominatim) = maps(true, vec![(503, "{}")], vec![(200, ORSK)]).await;
+
+        let cities = maps.find_cities("Орск").await.unwrap();
+
+        assert_eq!(cities.len(), 1);
+        assert_eq!(cities[0].actual(), "Орск");
+        assert_eq!((yandex.requests().len(), nominatim.requests().len()), (1, 1));
+
+        // The answer is cached, the geocoders are not asked again
+        maps.find_cities("орск").await.unwrap();
+        assert_eq!((yandex.requests().len(), nominatim.requests().len()), (1, 1));
+        assert_eq!(maps.cache_stats().hits, 1);
+    }
+
+    #[tokio::test]
+    async fn trusts_an_exhaustive_geocoder_that_the_city_does_not_exist() {
+        let (maps, yandex, nominatim) = maps(true, vec![(200, NOTHING)], vec![(200, ORSK)]).await;
+
+        for _ in 0..2 {
+            let error = maps.find_cities("Абырвалг").await.unwrap_err();
+            assert!(matches!(error, FetchingError::CityNotFound { .. }));
+        }
+
+        assert_eq!((yandex.requests().len(), nominatim.requests().len()), (1, 0));
+        assert_eq!(maps.cache_stats().not_found_hits, 1);
+    }
+
+    #[tokio::test]
+    async fn returns_the_last_error_when_every_geocoder_fails() {
+        let (maps, _yandex, _nominatim) = maps(true, vec![(429, "{}")], vec![(503, "{}")]).await;
+
+        let error = maps.find_cities("Орск").await.unwrap_err();
+
+        assert!(matches!(error, FetchingError::UnsuccessfulRequest { response_code: 503 }));
+    }
+
+    #[tokio::test]
+    async fn reverse_geocoding_falls_back_and_keeps_the_users_point() {
+        // The gazetteer would answer the reverse lookup itself
+        let (maps, _yandex, nominatim) = maps(false, vec![(429, "{}")], vec![(200, KHIMKI)]).await;
+        let point = Coordinates::new(37.43, 55.9);
+
+        let location = maps.get_city_by_coordinates(point.clone()).await.unwrap();
+
+        assert_eq!(location.actual(), "Химки");
+        assert_eq!(location.coordinates(), &Some(point));
+        assert_eq!(nominatim.requests().len(), 1);
+
+        // The city centre is cached for the people who type the name
+        let typed = maps.find_cities("Химки").await.unwrap();
+        assert_eq!(typed[0].coordinates(), &Some(Coordinates::new(37.4303, 55.8888)));
+        assert_eq!(nominatim.requests().len(), 1);
+    }
 }
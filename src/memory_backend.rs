@@ -0,0 +1,705 @@
+use crate::broadcast::{Broadcast, BroadcastAudience};
+use crate::database::{self, DatabaseBackend, DatabaseResult};
+use crate::profile::{
+    AgeFilter, Block, CachedLocation, DistanceFilter, HearingLevel, Like, Location, Match, PhotoId, Profile, ProfileVideo,
+    Report, ReportStatus, Sex, View,
+};
+use crate::user::*;
+use async_trait::async_trait;
+use mongodb::bson::Bson;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Keeps everything in memory, so nothing survives a restart.
+///
+/// Searches are evaluated with [Profile::matches_search] and likes and views never expire
+#[derive(Default)]
+pub struct MemoryBackend {
+    collections: Mutex<Collections>,
+}
+
+#[derive(Default)]
+struct Collections {
+    users: Vec<User>,
+    profiles: Vec<Profile>,
+    likes: Vec<Like>,
+    views: Vec<View>,
+    matches: Vec<Match>,
+    reports: Vec<Report>,
+    blocks: Vec<Block>,
+    bans: Vec<Ban>,
+    broadcasts: Vec<Broadcast>,
+    deletions: Vec<DeletionRecord>,
+    location_cache: Vec<CachedLocation>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    fn collections(&self) -> MutexGuard<'_, Collections> {
+        self.collections.lock().unwrap()
+    }
+
+    /// Applies the same `$set`/`$unset` as the MongoDB backend to the stored document of the profile
+    fn update_profile(&self, user_id: MyUserId, path: &str, value: Option<Bson>) -> DatabaseResult<()> {
+        let mut collections = self.collections();
+
+        let Some(profile) = collections.profiles.iter_mut().find(|p| *p.id() == user_id) else {
+            return Ok(());
+        };
+
+        *profile = database::update_profile(profile, path, value)?;
+        Ok(())
+    }
+
+    fn set_search_option(&self, user_id: MyUserId, key: &str, v: Option<Bson>) -> DatabaseResult<()> {
+        self.update_profile(user_id, &format!("settings.search_options.{key}"), v)
+    }
+
+    fn broadcast_recipients(&self, audience: &BroadcastAudience) -> Vec<MyUserId> {
+        let collections = self.collections();
+
+        collections
+            .profiles
+            .iter()
+            .filter(|profile| audience.matches(profile))
+            .filter(|profile| !collections.bans.iter().any(|ban| ban.id() == profile.id()))
+            .map(|profile| *profile.id())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl DatabaseBackend for MemoryBackend {
+    async fn add_user(&self, user: &User) -> DatabaseResult<()> {
+        self.collections().users.push(user.clone());
+
+        Ok(())
+    }
+
+    async fn add_profile(&self, profile: &Profile) -> DatabaseResult<()> {
+        self.collections().profiles.push(profile.clone());
+
+        Ok(())
+    }
+
+    async fn find_suggestion(
+        &self,
+        profile: &Profile,
+        viewed_profiles: &[View],
+        blocked_users: &[MyUserId],
+    ) -> DatabaseResult<Option<Profile>> {
+        Ok(self.collections()
+            .profiles
+            .iter()
+            .find(|candidate| profile.matches_search(candidate, viewed_profiles, blocked_users))
+            .cloned())
+    }
+
+    async fn set_profile_name(&self, user_id: MyUserId, v: &str) -> DatabaseResult<()> {
+        self.update_profile(user_id, "name", Some(Bson::from(v)))
+    }
+
+    async fn set_profile_age(&self, user_id: MyUserId, v: i64) -> DatabaseResult<()> {
+        self.update_profile(user_id, "age", Some(Bson::from(v)))
+    }
+
+    async fn set_profile_location(&self, user_id: MyUserId, v: Location) -> DatabaseResult<()> {
+        self.update_profile(user_id, "location", Some(Bson::from(v)))
+    }
+
+    async fn set_profile_hearing_level(&self, user_id: MyUserId, v: HearingLevel) -> DatabaseResult<()> {
+        self.update_profile(user_id, "hearing_level", Some(Bson::from(v)))
+    }
+
+    async fn set_profile_description(&self, user_id: MyUserId, v: Option<&str>) -> DatabaseResult<()> {
+        self.update_profile(user_id, "description", v.map(Bson::from))
+    }
+
+    async fn set_profile_video(&self, user_id: MyUserId, v: Option<ProfileVideo>) -> DatabaseResult<()> {
+        self.update_profile(user_id, "video", v.map(Bson::from))
+    }
+
+    async fn set_profile_photos(&self, user_id: MyUserId, pics: &[PhotoId]) -> DatabaseResult<()> {
+        self.update_profile(user_id, "photos", Some(Bson::from(pics)))
+    }
+
+    async fn set_profile_visibility(&self, user_id: MyUserId, show_up_in_search: bool) -> DatabaseResult<()> {
+        self.update_profile(user_id, "settings.show_up_in_search", Some(Bson::from(show_up_in_search)))
+    }
+
+    async fn set_search_age(&self, user_id: MyUserId, v: Option<AgeFilter>) -> DatabaseResult<()> {
+        self.set_search_option(user_id, "age", v.map(Bson::from))
+    }
+
+    async fn set_search_sex(&self, user_id: MyUserId, v: Option<Sex>) -> DatabaseResult<()> {
+        self.set_search_option(user_id, "sex", v.map(Bson::from))
+    }
+
+    async fn set_search_hearing_level(&self, user_id: MyUserId, v: Option<Vec<HearingLevel>>) -> DatabaseResult<()> {
+        self.set_search_option(user_id, "hearing_level", v.map(Bson::from))
+    }
+
+    async fn set_search_distance(&self, user_id: MyUserId, v: Option<DistanceFilter>) -> DatabaseResult<()> {
+        self.set_search_option(user_id, "distance", v.map(Bson::from))
+    }
+
+    async fn add_like(&self, like: Like) -> DatabaseResult<()> {
+        self.collections().likes.push(like);
+
+        Ok(())
+    }
+
+    async fn add_match(&self, value: Match) -> DatabaseResult<()> {
+        self.collections().matches.push(value);
+
+        Ok(())
+    }
+
+    async fn get_user_matches(&self, user_id: MyUserId) -> DatabaseResult<Vec<Match>> {
+        Ok(self.collections()
+            .matches
+            .iter()
+            .filter(|m| *m.first() == user_id || *m.second() == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn add_view(&self, view: View) -> DatabaseResult<()> {
+        self.collections().views.push(view);
+
+        Ok(())
+    }
+
+    async fn get_likes_to_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Like>> {
+        Ok(self.collections()
+            .likes
+            .iter()
+            .filter(|like| *like.to() == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_likes_from_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Like>> {
+        Ok(self.collections()
+            .likes
+            .iter()
+            .filter(|like| *like.from() == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_like(&self, from: MyUserId, to: MyUserId) -> DatabaseResult<Option<Like>> {
+        Ok(self.collections()
+            .likes
+            .iter()
+            .find(|like| *like.from() == from && *like.to() == to)
+            .cloned())
+    }
+
+    async fn remove_like(&self, from: MyUserId, to: MyUserId) -> DatabaseResult<()> {
+        let mut collections = self.collections();
+
+        if let Some(index) = collections.likes.iter().position(|like| *like.from() == from && *like.to() == to) {
+            collections.likes.remove(index);
+        }
+
+        Ok(())
+    }
+
+    async fn get_user_views(&self, user_id: MyUserId) -> DatabaseResult<Vec<View>> {
+        Ok(self.collections()
+            .views
+            .iter()
+            .filter(|view| *view.from() == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> DatabaseResult<Option<User>> {
+        Ok(self.collections()
+            .users
+            .iter()
+            .find(|user| user.username() == username)
+            .cloned())
+    }
+
+    async fn count_users(&self) -> DatabaseResult<u64> {
+        Ok(self.collections().users.len() as u64)
+    }
+
+    async fn count_profiles(&self) -> DatabaseResult<u64> {
+        Ok(self.collections().profiles.len() as u64)
+    }
+
+    async fn count_likes_since(&self, since: mongodb::bson::DateTime) -> DatabaseResult<u64> {
+        Ok(self.collections()
+            .views
+            .iter()
+            .filter(|view| *view.liked() && *view.timestamp() >= since)
+            .count() as u64)
+    }
+
+    async fn count_matches_since(&self, since: mongodb::bson::DateTime) -> DatabaseResult<u64> {
+        Ok(self.collections()
+            .matches
+            .iter()
+            .filter(|m| *m.timestamp() >= since)
+            .count() as u64)
+    }
+
+    async fn get_user(&self, id: MyUserId) -> DatabaseResult<Option<User>> {
+        Ok(self.collections().users.iter().find(|user| *user.id() == id).cloned())
+    }
+
+    async fn get_profile(&self, id: MyUserId) -> DatabaseResult<Option<Profile>> {
+        Ok(self.collections().profiles.iter().find(|profile| *profile.id() == id).cloned())
+    }
+
+    async fn add_report(&self, report: &Report) -> DatabaseResult<()> {
+        self.collections().reports.push(report.clone());
+
+        Ok(())
+    }
+
+    async fn count_reporters(&self, user_id: MyUserId) -> DatabaseResult<u64> {
+        let mut reporters: Vec<MyUserId> = self
+            .collections()
+            .reports
+            .iter()
+            .filter(|report| *report.to() == user_id && *report.status() == ReportStatus::Open)
+            .map(|report| *report.from())
+            .collect();
+
+        reporters.sort_by_key(|id| id.0);
+        reporters.dedup();
+        Ok(reporters.len() as u64)
+    }
+
+    async fn resolve_reports(&self, user_id: MyUserId, status: ReportStatus) -> DatabaseResult<()> {
+        self.collections()
+            .reports
+            .iter_mut()
+            .filter(|report| *report.to() == user_id && *report.status() == ReportStatus::Open)
+            .for_each(|report| report.set_status(status));
+
+        Ok(())
+    }
+
+    async fn get_reports_from_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Report>> {
+        Ok(self.collections()
+            .reports
+            .iter()
+            .filter(|report| *report.from() == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn add_block(&self, block: &Block) -> DatabaseResult<()> {
+        if self.is_blocked(*block.from(), *block.to()).await? {
+            return Ok(());
+        }
+
+        self.collections().blocks.push(block.clone());
+
+        Ok(())
+    }
+
+    async fn is_blocked(&self, first: MyUserId, second: MyUserId) -> DatabaseResult<bool> {
+        Ok(self.collections().blocks.iter().any(|block| {
+            (*block.from() == first && *block.to() == second) || (*block.from() == second && *block.to() == first)
+        }))
+    }
+
+    async fn get_blocked_users(&self, user_id: MyUserId) -> DatabaseResult<Vec<MyUserId>> {
+        Ok(self.collections()
+            .blocks
+            .iter()
+            .filter_map(|block| match (*block.from(), *block.to()) {
+                (from, to) if from == user_id => Some(to),
+                (from, to) if to == user_id => Some(from),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn get_blocks_from_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Block>> {
+        Ok(self.collections()
+            .blocks
+            .iter()
+            .filter(|block| *block.from() == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn add_ban(&self, ban: &Ban) -> DatabaseResult<()> {
+        if !self.is_banned(*ban.id()).await? {
+            self.collections().bans.push(ban.clone());
+        }
+
+        Ok(())
+    }
+
+    async fn ban_user(&self, user_id: MyUserId) -> DatabaseResult<()> {
+        self.add_ban(&Ban::new(user_id)).await?;
+        self.set_profile_visibility(user_id, false).await
+    }
+
+    async fn unban_user(&self, user_id: MyUserId) -> DatabaseResult<()> {
+        self.collections().bans.retain(|ban| *ban.id() != user_id);
+
+        Ok(())
+    }
+
+    async fn count_broadcast_recipients(&self, audience: &BroadcastAudience) -> DatabaseResult<u64> {
+        Ok(self.broadcast_recipients(audience).len() as u64)
+    }
+
+    async fn get_broadcast_recipients(&self, audience: &BroadcastAudience) -> DatabaseResult<Vec<MyUserId>> {
+        Ok(self.broadcast_recipients(audience))
+    }
+
+    async fn add_broadcast(&self, broadcast: &Broadcast) -> DatabaseResult<()> {
+        self.collections().broadcasts.push(broadcast.clone());
+
+        Ok(())
+    }
+
+    async fn update_broadcast(&self, broadcast: &Broadcast) -> DatabaseResult<()> {
+        let mut collections = self.collections();
+
+        if let Some(stored) = collections.broadcasts.iter_mut().find(|b| b.id() == broadcast.id()) {
+            *stored = broadcast.clone();
+        }
+
+        Ok(())
+    }
+
+    async fn is_banned(&self, user_id: MyUserId) -> DatabaseResult<bool> {
+        Ok(self.collections().bans.iter().any(|ban| *ban.id() == user_id))
+    }
+
+    async fn cache_location(&self, cached: &CachedLocation) -> DatabaseResult<()> {
+        let mut collections = self.collections();
+
+        remove_where(&mut collections.location_cache, |el| el.key() == cached.key() || el.is_expired());
+        collections.location_cache.push(cached.clone());
+
+        Ok(())
+    }
+
+    async fn get_cached_location(&self, key: &str) -> DatabaseResult<Option<CachedLocation>> {
+        Ok(self.collections()
+            .location_cache
+            .iter()
+            .find(|cached| cached.key() == key && !cached.is_expired())
+            .cloned())
+    }
+
+    async fn delete_user_data(&self, user_id: MyUserId) -> DatabaseResult<DeletionRecord> {
+        let mut collections = self.collections();
+
+        let users = remove_where(&mut collections.users, |user| *user.id() == user_id);
+        let profiles = remove_where(&mut collections.profiles, |profile| *profile.id() == user_id);
+        let likes = remove_where(&mut collections.likes, |like| *like.from() == user_id || *like.to() == user_id);
+        let views = remove_where(&mut collections.views, |view| *view.from() == user_id || *view.to() == user_id);
+
+        remove_where(&mut collections.reports, |report| *report.from() == user_id);
+        remove_where(&mut collections.blocks, |block| *block.from() == user_id);
+        remove_where(&mut collections.matches, |m| *m.first() == user_id || *m.second() == user_id);
+
+        let record = DeletionRecord::new(users, profiles, likes, views);
+        collections.deletions.push(record.clone());
+
+        Ok(record)
+    }
+
+    async fn add_deletion(&self, record: &DeletionRecord) -> DatabaseResult<()> {
+        self.collections().deletions.push(record.clone());
+
+        Ok(())
+    }
+}
+
+/// Removes matching elements and returns how many were removed
+fn remove_where<T>(list: &mut Vec<T>, predicate: impl Fn(&T) -> bool) -> u64 {
+    let before = list.len();
+    list.retain(|el| !predicate(el));
+    (before - list.len()) as u64
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::application::Configuration;
+    use crate::common;
+    use crate::database::Database;
+    use crate::profile::ReportReason;
+    use mongodb::bson::Document;
+    use serde_json::json;
+
+    /// Evaluates the subset of MongoDB queries used by [Profile::search_filter] against a document
+    fn matches(document: &Document, filter: &Document) -> bool {
+        filter.iter().all(|(key, condition)| match key.as_str() {
+            "$and" => operands(condition).all(|filter| matches(document, filter)),
+            "$or" => operands(condition).any(|filter| matches(document, filter)),
+            "$nor" => !operands(condition).any(|filter| matches(document, filter)),
+            path => matches_field(lookup(document, path), condition),
+        })
+    }
+
+    fn operands(condition: &Bson) -> impl Iterator<Item = &Document> {
+        condition.as_array().unwrap().iter().map(|filter| filter.as_document().unwrap())
+    }
+
+    fn lookup<'a>(document: &'a Document, path: &str) -> Option<&'a Bson> {
+        let mut value = None;
+        let mut current = Some(document);
+
+        for key in path.split('.') {
+            value = current?.get(key);
+            current = value.and_then(Bson::as_document);
+        }
+
+        value
+    }
+
+    fn matches_field(value: Option<&Bson>, condition: &Bson) -> bool {
+        let operators = match condition {
+            Bson::Document(operators) if operators.keys().all(|key| key.starts_with('$')) => operators,
+            expected => return value.is_some_and(|value| equals(value, expected)),
+        };
+
+        operators.iter().all(|(operator, argument)| match operator.as_str() {
+            "$exists" => value.is_some() == argument.as_bool().unwrap(),
+            "$ne" => !value.is_some_and(|value| equals(value, argument)),
+            "$in" => value.is_some_and(|value| argument.as_array().unwrap().iter().any(|el| equals(value, el))),
+            "$nin" => !value.is_some_and(|value| argument.as_array().unwrap().iter().any(|el| equals(value, el))),
+            "$gte" => value.and_then(number).is_some_and(|value| value >= number(argument).unwrap()),
+            "$lte" => value.and_then(number).is_some_and(|value| value <= number(argument).unwrap()),
+            "$geoWithin" => value.is_some_and(|value| within_sphere(value, argument)),
+            operator => panic!("unsupported operator `{operator}`"),
+        })
+    }
+
+    /// Arrays match if any of their elements does, numbers are compared by value
+    fn equals(value: &Bson, expected: &Bson) -> bool {
+        match (value, number(value), number(expected)) {
+            (_, Some(a), Some(b)) => a == b,
+            (Bson::Array(elements), ..) => elements.iter().any(|el| equals(el, expected)),
+            _ => value == expected,
+        }
+    }
+
+    fn number(value: &Bson) -> Option<f64> {
+        match value {
+            Bson::Int32(v) => Some(*v as f64),
+            Bson::Int64(v) => Some(*v as f64),
+            Bson::Double(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn coordinates(value: &Bson) -> (f64, f64) {
+        let coordinates = value.as_array().unwrap();
+        (number(&coordinates[0]).unwrap(), number(&coordinates[1]).unwrap())
+    }
+
+    /// `{ "$centerSphere": [[longitude, latitude], radians] }` over a GeoJSON point
+    fn within_sphere(value: &Bson, argument: &Bson) -> bool {
+        let sphere = argument.as_document().unwrap().get_array("$centerSphere").unwrap();
+        let (lon_a, lat_a) = coordinates(&sphere[0]);
+        let radians = number(&sphere[1]).unwrap();
+        let (lon_b, lat_b) = coordinates(value.as_document().unwrap().get("coordinates").unwrap());
+
+        let (lat_a, lat_b) = (lat_a.to_radians(), lat_b.to_radians());
+        let a = ((lat_b - lat_a) / 2.0).sin().powi(2)
+            + lat_a.cos() * lat_b.cos() * ((lon_b - lon_a).to_radians() / 2.0).sin().powi(2);
+
+        2.0 * a.sqrt().asin() <= radians
+    }
+
+    /// Small deterministic generator, the fixtures are the same on every run
+    pub(crate) struct Fixtures(pub u64);
+
+    impl Fixtures {
+        pub fn pick<'a, T>(&mut self, values: &'a [T]) -> &'a T {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            &values[(self.0 >> 33) as usize % values.len()]
+        }
+
+        pub fn profile(&mut self, id: u64) -> Profile {
+            let location = self.pick(&[
+                json!({ "displayed": "Москва", "actual": "Москва", "coordinates": point(37.62, 55.75) }),
+                json!({ "displayed": "Москва", "actual": "Москва" }),
+                json!({ "displayed": "Химки", "actual": "Химки", "coordinates": point(37.44, 55.89) }),
+                json!({ "displayed": "Химки", "actual": "Химки" }),
+                json!({ "displayed": "Тверь", "actual": "Тверь", "coordinates": point(35.91, 56.86) }),
+            ]).clone();
+
+            let mut search_options = serde_json::Map::new();
+
+            if let Some(age) = self.pick(&[None, Some((18, 25)), Some((24, 40)), Some((30, 60))]) {
+                search_options.insert("age".into(), json!({ "lowest": age.0, "greatest": age.1 }));
+            }
+            if let Some(sex) = self.pick(&[None, Some("Male"), Some("Female")]) {
+                search_options.insert("sex".into(), json!(sex));
+            }
+            if let Some(levels) = self.pick(&[None, Some(vec!["HearingImpaired"]), Some(vec!["CompletelyDeaf", "Hearing"])]) {
+                search_options.insert("hearing_level".into(), json!(levels));
+            }
+            if let Some(max_meters) = self.pick(&[None, Some(30_000), Some(200_000)]) {
+                search_options.insert("distance".into(), json!({ "max_meters": max_meters }));
+            }
+
+            serde_json::from_value(json!({
+                "id": id,
+                "photos": ["photo"],
+                "name": format!("Профиль {id}"),
+                "age": self.pick(&[18, 24, 25, 33, 45]),
+                "sex": self.pick(&["Male", "Female"]),
+                "hearing_level": self.pick(&["CompletelyDeaf", "HearingImpaired", "Hearing"]),
+                "location": location,
+                "settings": {
+                    "show_up_in_search": *self.pick(&[true, true, true, false]),
+                    "search_options": search_options,
+                },
+            }))
+            .unwrap()
+        }
+    }
+
+    fn point(longitude: f64, latitude: f64) -> serde_json::Value {
+        json!({ "type": "Point", "coordinates": [longitude, latitude] })
+    }
+
+    pub(crate) fn fixtures() -> Vec<Profile> {
+        let mut fixtures = Fixtures(42);
+        (1..=80).map(|id| fixtures.profile(id)).collect()
+    }
+
+    #[test]
+    fn matches_search_agrees_with_search_filter() {
+        let profiles = fixtures();
+        let mut fixtures = Fixtures(7);
+        let mut found = 0;
+
+        for searcher in &profiles {
+            let viewed: Vec<_> = profiles.iter()
+                .filter(|_| *fixtures.pick(&[false, false, false, true]))
+                .map(|viewed| View::new(*searcher.id(), *viewed.id(), false))
+                .collect();
+            let blocked: Vec<_> = profiles.iter()
+                .filter(|_| *fixtures.pick(&[false, false, false, false, false, true]))
+                .map(|blocked| *blocked.id())
+                .collect();
+
+            let filter = searcher.search_filter(&viewed, &blocked);
+
+            for candidate in &profiles {
+                let document = mongodb::bson::to_document(candidate).unwrap();
+                let expected = matches(&document, &filter);
+
+                assert_eq!(
+                    searcher.matches_search(candidate, &viewed, &blocked),
+                    expected,
+                    "searcher {searcher:?}\ncandidate {candidate:?}"
+                );
+
+                found += usize::from(expected);
+            }
+        }
+
+        // Otherwise the fixtures would only check that nothing matches
+        assert!(found > 100, "only {found} matches");
+    }
+
+    #[test]
+    fn checks_the_age_range_of_both_sides() {
+        let profiles = fixtures();
+        let young = profiles.iter().find(|p| *p.age() == 18).unwrap();
+
+        for candidate in &profiles {
+            let document = mongodb::bson::to_document(candidate).unwrap();
+
+            if candidate.settings().search_options().age().as_ref().is_some_and(|af| *af.lowest() > 18) {
+                assert!(!young.matches_search(candidate, &[], &[]));
+                assert!(!matches(&document, &young.search_filter(&[], &[])));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_the_suggestions_the_filter_finds() {
+        let profiles = fixtures();
+        let backend = MemoryBackend::new();
+
+        for profile in &profiles {
+            backend.add_profile(profile).await.unwrap();
+        }
+
+        for searcher in profiles.iter().take(10) {
+            let filter = searcher.search_filter(&[], &[]);
+            let mut expected: Vec<_> = profiles.iter()
+                .filter(|candidate| matches(&mongodb::bson::to_document(candidate).unwrap(), &filter))
+                .map(|candidate| candidate.id().0)
+                .collect();
+
+            let mut viewed = vec![];
+            while let Some(suggestion) = backend.find_suggestion(searcher, &viewed, &[]).await.unwrap() {
+                viewed.push(View::new(*searcher.id(), *suggestion.id(), false));
+            }
+
+            let mut suggested: Vec<_> = viewed.iter().map(|view| view.to().0).collect();
+            expected.sort_unstable();
+            suggested.sort_unstable();
+
+            assert_eq!(suggested, expected, "searcher {}", searcher.id());
+        }
+    }
+
+    #[tokio::test]
+    async fn search_option_updates_change_the_suggestions() {
+        let backend = MemoryBackend::new();
+        let mut fixtures = Fixtures(1);
+        let (searcher, candidate) = (fixtures.profile(1), fixtures.profile(2));
+
+        backend.add_profile(&searcher).await.unwrap();
+        backend.add_profile(&candidate).await.unwrap();
+
+        let other_sex = match candidate.sex() {
+            Sex::Male => Sex::Female,
+            Sex::Female => Sex::Male,
+        };
+        backend.set_search_sex(*searcher.id(), Some(other_sex)).await.unwrap();
+
+        let searcher = backend.get_profile(*searcher.id()).await.unwrap().unwrap();
+        assert!(backend.find_suggestion(&searcher, &[], &[]).await.unwrap().is_none());
+
+        backend.set_search_distance(*searcher.id(), None).await.unwrap();
+        let searcher = backend.get_profile(*searcher.id()).await.unwrap().unwrap();
+        assert!(searcher.settings().search_options().distance().is_none());
+    }
+
+    #[tokio::test]
+    async fn reports_outlive_the_deletion_of_the_reported_user() {
+        let backend = MemoryBackend::new();
+        let db = Database::new(backend.clone());
+        let config = Configuration::parse_configuration_string(r#"{ "bot_token": "test", "report_threshold": 3 }"#);
+        let mut fixtures = Fixtures(3);
+
+        backend.add_profile(&fixtures.profile(1)).await.unwrap();
+        for reporter in 2..=4 {
+            backend.add_report(&Report::new(reporter, 1, ReportReason::Fake)).await.unwrap();
+        }
+        backend.add_report(&Report::new(1, 2, ReportReason::Spam)).await.unwrap();
+
+        backend.delete_user_data(MyUserId(1)).await.unwrap();
+        backend.add_profile(&fixtures.profile(1)).await.unwrap();
+
+        assert!(common::is_on_hold(&db, &config, MyUserId(1)).await.unwrap());
+        // Reports the deleted user filed are gone with them
+        assert_eq!(backend.count_reporters(MyUserId(2)).await.unwrap(), 0);
+    }
+}
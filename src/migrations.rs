@@ -0,0 +1,277 @@
+use crate::profile::DEFAULT_MAX_DISTANCE_METERS;
+use futures::future::BoxFuture;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::options::IndexOptions;
+use mongodb::IndexModel;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+type MigrationResult = mongodb::error::Result<()>;
+
+/// A one-time change of the database schema.
+///
+/// Applied versions are kept in the `migrations` collection, so every migration runs only once
+struct Migration {
+    version: i32,
+    description: &'static str,
+    apply: for<'a> fn(&'a mongodb::Database) -> BoxFuture<'a, MigrationResult>,
+}
+
+/// Every migration ever written, in the order they are applied.
+///
+/// Never change or remove an applied migration, add a new one instead
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "unique user and profile ids",
+        apply: unique_ids,
+    },
+    Migration {
+        version: 2,
+        description: "indexes for likes, views, reports, blocks and matches",
+        apply: relation_indexes,
+    },
+    Migration {
+        version: 3,
+        description: "indexes for profile search",
+        apply: search_indexes,
+    },
+    Migration {
+        version: 4,
+        description: "default search distance for profiles created before it existed",
+        apply: default_search_distance,
+    },
+    Migration {
+        version: 5,
+        description: "location cache keyed by normalized names, with expiry",
+        apply: location_cache_keys,
+    },
+    Migration {
+        version: 6,
+        description: "location cache without the coordinates of users who shared their location",
+        apply: clear_location_cache,
+    },
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MigrationRecord {
+    version: i32,
+    description: String,
+    applied: mongodb::bson::DateTime,
+}
+
+/// Applies the migrations missing from the `migrations` collection
+pub async fn run(database: &mongodb::Database) {
+    let records = database.collection::<MigrationRecord>("migrations");
+
+    records
+        .create_index(unique(doc! { "version": 1 }), None)
+        .await
+        .unwrap();
+
+    let applied: Vec<i32> = records
+        .find(None, None)
+        .await
+        .unwrap()
+        .map_ok(|record| record.version)
+        .try_collect()
+        .await
+        .unwrap();
+
+    for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+        log::info!("applying migration {}: {}", migration.version, migration.description);
+
+        if let Err(error) = (migration.apply)(database).await {
+            panic!("Unable to apply migration {}: {error}", migration.version);
+        }
+
+        records
+            .insert_one(
+                MigrationRecord {
+                    version: migration.version,
+                    description: migration.description.to_owned(),
+                    applied: mongodb::bson::DateTime::now(),
+                },
+                None,
+            )
+            .await
+            .unwrap();
+    }
+}
+
+fn unique(keys: Document) -> IndexModel {
+    IndexModel::builder()
+        .keys(keys)
+        .options(IndexOptions::builder().unique(true).build())
+        .build()
+}
+
+fn index(keys: Document) -> IndexModel {
+    IndexModel::builder().keys(keys).build()
+}
+
+async fn create_indexes(
+    database: &mongodb::Database,
+    collection: &str,
+    indexes: Vec<IndexModel>,
+) -> MigrationResult {
+    database
+        .collection::<Document>(collection)
+        .create_indexes(indexes, None)
+        .await?;
+    Ok(())
+}
+
+/// Nothing prevented duplicate ids before, only the newest document of each id is kept so the index can be built
+fn unique_ids(database: &mongodb::Database) -> BoxFuture<'_, MigrationResult> {
+    Box::pin(async move {
+        for collection in ["users", "profiles", "bans"] {
+            remove_duplicate_ids(database, collection).await?;
+            create_indexes(database, collection, vec![unique(doc! { "id": 1 })]).await?;
+        }
+        Ok(())
+    })
+}
+
+async fn remove_duplicate_ids(database: &mongodb::Database, collection: &str) -> MigrationResult {
+    let collection = database.collection::<Document>(collection);
+
+    let duplicates: Vec<Document> = collection
+        .aggregate(
+            [
+                doc! { "$sort": { "_id": -1 } },
+                doc! { "$group": { "_id": "$id", "documents": { "$push": "$_id" } } },
+                doc! { "$match": { "documents.1": { "$exists": true } } },
+            ],
+            None,
+        )
+        .await?
+        .try_collect()
+        .await?;
+
+    for duplicate in &duplicates {
+        // ObjectIds grow with time, the first one is the newest
+        let older = duplicate.get_array("documents").map_or(&[][..], |ids| &ids[1..]);
+
+        collection
+            .delete_many(doc! { "_id": { "$in": older } }, None)
+            .await?;
+
+        log::warn!(
+            "`{}` had {} documents with id {}, only the newest is kept",
+            collection.name(),
+            older.len() + 1,
+            duplicate.get("_id").map_or_else(|| "null".to_owned(), ToString::to_string),
+        );
+    }
+
+    Ok(())
+}
+
+fn relation_indexes(database: &mongodb::Database) -> BoxFuture<'_, MigrationResult> {
+    Box::pin(async move {
+        create_indexes(
+            database,
+            "likes",
+            vec![index(doc! { "to": 1, "timestamp": 1 }), index(doc! { "from": 1, "to": 1 })],
+        )
+        .await?;
+        create_indexes(database, "views", vec![index(doc! { "from": 1, "to": 1 })]).await?;
+        create_indexes(
+            database,
+            "reports",
+            vec![index(doc! { "to": 1, "status": 1 }), index(doc! { "from": 1 })],
+        )
+        .await?;
+        create_indexes(
+            database,
+            "blocks",
+            vec![index(doc! { "from": 1, "to": 1 }), index(doc! { "to": 1 })],
+        )
+        .await?;
+        create_indexes(
+            database,
+            "matches",
+            vec![index(doc! { "first": 1 }), index(doc! { "second": 1 }), index(doc! { "timestamp": 1 })],
+        )
+        .await?;
+        Ok(())
+    })
+}
+
+fn search_indexes(database: &mongodb::Database) -> BoxFuture<'_, MigrationResult> {
+    Box::pin(async move {
+        create_indexes(
+            database,
+            "profiles",
+            vec![
+                index(doc! {
+                    "settings.show_up_in_search": 1,
+                    "location.actual": 1,
+                    "sex": 1,
+                    "hearing_level": 1,
+                    "age": 1,
+                }),
+                index(doc! { "location.coordinates": "2dsphere" }),
+            ],
+        )
+        .await
+    })
+}
+
+/// "Only my city" is stored as `null`, so the profiles without the key were created before the distance existed
+fn default_search_distance(database: &mongodb::Database) -> BoxFuture<'_, MigrationResult> {
+    Box::pin(async move {
+        let result = database
+            .collection::<Document>("profiles")
+            .update_many(
+                doc! { "settings.search_options.distance": { "$exists": false } },
+                doc! {
+                    "$set": {
+                        "settings.search_options.distance": {
+                            "max_meters": DEFAULT_MAX_DISTANCE_METERS as i64
+                        }
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        log::info!("search distance set for {} profiles", result.modified_count);
+        Ok(())
+    })
+}
+
+/// Old entries were keyed by the raw input and never expired, it's only a cache so they are dropped
+fn location_cache_keys(database: &mongodb::Database) -> BoxFuture<'_, MigrationResult> {
+    Box::pin(async move {
+        database.collection::<Document>("location_cache").drop(None).await?;
+
+        create_indexes(
+            database,
+            "location_cache",
+            vec![
+                unique(doc! { "key": 1 }),
+                IndexModel::builder()
+                    .keys(doc! { "expires_at": 1 })
+                    .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+                    .build(),
+            ],
+        )
+        .await
+    })
+}
+
+/// Reverse geocoded cities were cached with the exact point the user shared instead of the city centre
+fn clear_location_cache(database: &mongodb::Database) -> BoxFuture<'_, MigrationResult> {
+    Box::pin(async move {
+        let result = database
+            .collection::<Document>("location_cache")
+            .delete_many(doc! {}, None)
+            .await?;
+
+        log::info!("{} cached locations removed", result.deleted_count);
+        Ok(())
+    })
+}
@@ -0,0 +1,789 @@
+use crate::broadcast::{Broadcast, BroadcastAudience};
+use crate::database::{DatabaseBackend, DatabaseError, DatabaseResult};
+use crate::migrations;
+use crate::profile::{
+    AgeFilter, Block, CachedLocation, DistanceFilter, HearingLevel, Like, Location, Match, PhotoId, Profile, ProfileVideo,
+    Report, ReportStatus, Sex, View,
+};
+use crate::user::*;
+use async_trait::async_trait;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::options::{FindOptions, IndexOptions, ReplaceOptions};
+use mongodb::IndexModel;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Keeps everything in MongoDB.
+///
+/// Likes and views are removed by the database itself through the TTL indexes
+/// made in [MongoBackend::create_indexes]
+pub struct MongoBackend {
+    database: mongodb::Database,
+}
+
+impl MongoBackend {
+    pub fn new(client: mongodb::Client, database_name: &str) -> Arc<Self> {
+        Arc::new(Self {
+            database: client.database(database_name),
+        })
+    }
+
+    /// Applies schema migrations, which also create the indexes the queries rely on
+    pub async fn migrate(&self) {
+        migrations::run(&self.database).await;
+    }
+
+    /// Creates the TTL indexes for likes and views, or updates their lifetime if they already exist.
+    ///
+    /// Unlike other indexes they depend on configuration, so they aren't made by migrations
+    pub async fn create_indexes(&self, like_ttl: Duration, view_ttl: Duration) -> DatabaseResult<()> {
+        self.ensure_ttl_index("likes", like_ttl).await?;
+        self.ensure_ttl_index("views", view_ttl).await
+    }
+
+    /// Makes the documents of the collection expire `ttl` after their `timestamp`
+    async fn ensure_ttl_index(&self, collection: &str, ttl: Duration) -> DatabaseResult<()> {
+        let index = IndexModel::builder()
+            .keys(doc! { "timestamp": 1 })
+            .options(IndexOptions::builder().expire_after(ttl).build())
+            .build();
+
+        let created = self.database
+            .collection::<Document>(collection)
+            .create_index(index, None)
+            .await;
+
+        match created {
+            Ok(_) => {}
+            // The index exists with another lifetime, which can only be changed with `collMod`
+            Err(error) if is_index_options_conflict(&error) => {
+                self.database
+                    .run_command(
+                        doc! {
+                            "collMod": collection,
+                            "index": {
+                                "keyPattern": { "timestamp": 1 },
+                                "expireAfterSeconds": ttl.as_secs() as i64,
+                            }
+                        },
+                        None,
+                    )
+                    .await?;
+            }
+            Err(error) => return Err(error.into()),
+        }
+
+        log::info!("`{collection}` expire after {} hours", ttl.as_secs() / 60 / 60);
+
+        Ok(())
+    }
+
+    /// Sets `settings.search_options.<key>` or unsets it if the value is _None_
+    async fn set_search_option(&self, user_id: MyUserId, key: &str, v: Option<Bson>) -> DatabaseResult<()> {
+        let key = format!("settings.search_options.{key}");
+
+        let update = match v {
+            Some(value) => doc! { "$set": { key: value } },
+            None => doc! { "$unset": { key: "" } },
+        };
+
+        self.profiles_collection()
+            .update_one(doc! { "id": user_id }, update, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_banned_users(&self) -> DatabaseResult<Vec<MyUserId>> {
+        let bans: Vec<Ban> = self
+            .bans_collection()
+            .find(None, None)
+            .await?
+            .try_collect()
+            .await?;
+
+        Ok(bans.iter().map(|ban| *ban.id()).collect())
+    }
+
+    /// Filter for profiles who receive the broadcast, banned users never get it
+    async fn broadcast_filter(&self, audience: &BroadcastAudience) -> DatabaseResult<Document> {
+        Ok(doc! {
+            "$and": [
+                audience.filter(),
+                { "id": { "$nin": self.get_banned_users().await? } }
+            ]
+        })
+    }
+
+    /// Copies every record into another backend as it is, the target has to be empty.
+    ///
+    /// Used once to move a deployment off MongoDB. Dialogue states are not data and aren't copied,
+    /// users who were in the middle of something start from the menu
+    pub async fn copy_into(&self, target: &dyn DatabaseBackend) -> DatabaseResult<()> {
+        let users = target.count_users().await?;
+
+        if users > 0 {
+            return Err(DatabaseError::TargetNotEmpty { users });
+        }
+
+        for user in Self::find_all(self.users_collection()).await? {
+            target.add_user(&user).await?;
+        }
+        for profile in Self::find_all(self.profiles_collection()).await? {
+            target.add_profile(&profile).await?;
+        }
+        for like in Self::find_all(self.likes_collection()).await? {
+            target.add_like(like).await?;
+        }
+        for view in Self::find_all(self.views_collection()).await? {
+            target.add_view(view).await?;
+        }
+        for value in Self::find_all(self.matches_collection()).await? {
+            target.add_match(value).await?;
+        }
+        for report in Self::find_all(self.reports_collection()).await? {
+            target.add_report(&report).await?;
+        }
+        for block in Self::find_all(self.blocks_collection()).await? {
+            target.add_block(&block).await?;
+        }
+        for ban in Self::find_all(self.bans_collection()).await? {
+            target.add_ban(&ban).await?;
+        }
+        for broadcast in Self::find_all(self.broadcasts_collection()).await? {
+            target.add_broadcast(&broadcast).await?;
+        }
+        for record in Self::find_all(self.deletions_collection()).await? {
+            target.add_deletion(&record).await?;
+        }
+        for cached in Self::find_all(self.location_cache_collection()).await? {
+            target.cache_location(&cached).await?;
+        }
+
+        log::info!(
+            "copied {} users and {} profiles",
+            target.count_users().await?,
+            target.count_profiles().await?
+        );
+
+        Ok(())
+    }
+
+    async fn find_all<T>(collection: mongodb::Collection<T>) -> DatabaseResult<Vec<T>>
+    where
+        T: serde::de::DeserializeOwned + Unpin + Send + Sync,
+    {
+        Ok(collection
+            .find(None, None)
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    #[inline]
+    fn users_collection(&self) -> mongodb::Collection<User> {
+        log::trace!("users collection access requested");
+        self.database.collection("users")
+    }
+
+    #[inline]
+    fn profiles_collection(&self) -> mongodb::Collection<Profile> {
+        log::trace!("profiles collection access requested");
+        self.database.collection("profiles")
+    }
+
+    #[inline]
+    fn likes_collection(&self) -> mongodb::Collection<Like> {
+        log::trace!("likes collection access requested");
+        self.database.collection("likes")
+    }
+
+    #[inline]
+    fn views_collection(&self) -> mongodb::Collection<View> {
+        log::trace!("views collection access requested");
+        self.database.collection("views")
+    }
+
+    #[inline]
+    fn reports_collection(&self) -> mongodb::Collection<Report> {
+        log::trace!("reports collection access requested");
+        self.database.collection("reports")
+    }
+
+    #[inline]
+    fn broadcasts_collection(&self) -> mongodb::Collection<Broadcast> {
+        log::trace!("broadcasts collection access requested");
+        self.database.collection("broadcasts")
+    }
+
+    #[inline]
+    fn matches_collection(&self) -> mongodb::Collection<Match> {
+        log::trace!("matches collection access requested");
+        self.database.collection("matches")
+    }
+
+    #[inline]
+    fn blocks_collection(&self) -> mongodb::Collection<Block> {
+        log::trace!("blocks collection access requested");
+        self.database.collection("blocks")
+    }
+
+    #[inline]
+    fn bans_collection(&self) -> mongodb::Collection<Ban> {
+        log::trace!("bans collection access requested");
+        self.database.collection("bans")
+    }
+
+    #[inline]
+    fn location_cache_collection(&self) -> mongodb::Collection<CachedLocation> {
+        log::trace!("location cache collection access requested");
+        self.database.collection("location_cache")
+    }
+
+    #[inline]
+    fn deletions_collection(&self) -> mongodb::Collection<DeletionRecord> {
+        log::trace!("deletions collection access requested");
+        self.database.collection("deletions")
+    }
+}
+
+#[async_trait]
+impl DatabaseBackend for MongoBackend {
+    async fn add_user(&self, user: &User) -> DatabaseResult<()> {
+        log::trace!("new record of the user with id `{}` created", user.id());
+
+        self.users_collection()
+            .insert_one(user, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn add_profile(&self, profile: &Profile) -> DatabaseResult<()> {
+        log::trace!("new record of the profile with id `{}` created", profile.id());
+
+        self.profiles_collection()
+            .insert_one(profile, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn find_suggestion(
+        &self,
+        profile: &Profile,
+        viewed_profiles: &[View],
+        blocked_users: &[MyUserId],
+    ) -> DatabaseResult<Option<Profile>> {
+        let filter = profile.search_filter(viewed_profiles, blocked_users);
+
+        let mut cursor = self.profiles_collection().find(filter, None).await?;
+
+        Ok(cursor.try_next().await?)
+    }
+
+    async fn set_profile_name(&self, user_id: MyUserId, v: &str) -> DatabaseResult<()> {
+        self.profiles_collection()
+            .update_one(
+                doc! { "id": user_id },
+                doc! { "$set": { "name": v } },
+                None
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_profile_age(&self, user_id: MyUserId, v: i64) -> DatabaseResult<()> {
+        self.profiles_collection()
+            .update_one(
+                doc! { "id": user_id },
+                doc! { "$set": { "age": v } },
+                None
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_profile_location(&self, user_id: MyUserId, v: Location) -> DatabaseResult<()> {
+        self.profiles_collection()
+            .update_one(
+                doc! { "id": user_id },
+                doc! { "$set": { "location": v } },
+                None
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_profile_hearing_level(&self, user_id: MyUserId, v: HearingLevel) -> DatabaseResult<()> {
+        self.profiles_collection()
+            .update_one(
+                doc! { "id": user_id },
+                doc! { "$set": { "hearing_level": v } },
+                None
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_profile_description(&self, user_id: MyUserId, v: Option<&str>) -> DatabaseResult<()> {
+        match v {
+            Some(description) => {
+                self.profiles_collection()
+                    .update_one(
+                        doc! { "id": user_id },
+                        doc! { "$set": { "description": description } },
+                        None
+                    )
+                    .await?;
+            }
+            None => {
+                self.profiles_collection()
+                    .update_one(
+                        doc! { "id": user_id },
+                        doc! { "$unset": { "description": "" } },
+                        None
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn set_profile_video(&self, user_id: MyUserId, v: Option<ProfileVideo>) -> DatabaseResult<()> {
+        let update = match v {
+            Some(video) => doc! { "$set": { "video": video } },
+            None => doc! { "$unset": { "video": "" } },
+        };
+
+        self.profiles_collection()
+            .update_one(doc! { "id": user_id }, update, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_profile_photos(&self, user_id: MyUserId, pics: &[PhotoId]) -> DatabaseResult<()> {
+        self.profiles_collection()
+            .update_one(
+                doc! { "id": user_id },
+                doc! { "$set": { "photos": pics } },
+                None
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_profile_visibility(&self, user_id: MyUserId, show_up_in_search: bool) -> DatabaseResult<()> {
+        self.profiles_collection()
+            .update_one(
+                doc! { "id": user_id },
+                doc! { "$set": { "settings.show_up_in_search": show_up_in_search } },
+                None
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_search_age(&self, user_id: MyUserId, v: Option<AgeFilter>) -> DatabaseResult<()> {
+        self.set_search_option(user_id, "age", v.map(Bson::from)).await
+    }
+
+    async fn set_search_sex(&self, user_id: MyUserId, v: Option<Sex>) -> DatabaseResult<()> {
+        self.set_search_option(user_id, "sex", v.map(Bson::from)).await
+    }
+
+    async fn set_search_hearing_level(&self, user_id: MyUserId, v: Option<Vec<HearingLevel>>) -> DatabaseResult<()> {
+        self.set_search_option(user_id, "hearing_level", v.map(Bson::from)).await
+    }
+
+    /// "Only my city" is stored as `null` instead of being unset,
+    /// so it isn't taken for a profile created before the distance existed
+    async fn set_search_distance(&self, user_id: MyUserId, v: Option<DistanceFilter>) -> DatabaseResult<()> {
+        self.set_search_option(user_id, "distance", Some(v.map_or(Bson::Null, Bson::from))).await
+    }
+
+    async fn add_like(&self, like: Like) -> DatabaseResult<()> {
+        self.likes_collection()
+            .insert_one(like, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn add_match(&self, value: Match) -> DatabaseResult<()> {
+        self.matches_collection()
+            .insert_one(value, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_user_matches(&self, user_id: MyUserId) -> DatabaseResult<Vec<Match>> {
+        let cursor = self
+            .matches_collection()
+            .find(doc! { "$or": [ { "first": user_id }, { "second": user_id } ] }, None)
+            .await?;
+
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn add_view(&self, view: View) -> DatabaseResult<()> {
+        self.views_collection()
+            .insert_one(view, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_likes_to_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Like>> {
+        let cursor = self
+            .likes_collection()
+            .find(
+                doc! { "to": user_id },
+                FindOptions::builder().sort(doc! { "timestamp": 1 }).build(),
+            )
+            .await?;
+
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn get_likes_from_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Like>> {
+        let cursor = self
+            .likes_collection()
+            .find(
+                doc! { "from": user_id },
+                FindOptions::builder().sort(doc! { "timestamp": 1 }).build(),
+            )
+            .await?;
+
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_like(
+        &self,
+        from: MyUserId,
+        to: MyUserId,
+    ) -> DatabaseResult<Option<Like>> {
+        Ok(self.likes_collection()
+            .find_one(
+                doc! {
+                    "$and": [
+                        { "from": from },
+                        { "to": to }
+                    ]
+                },
+                None,
+            )
+            .await?)
+    }
+
+    async fn remove_like(&self, from: MyUserId, to: MyUserId) -> DatabaseResult<()> {
+        self.likes_collection()
+            .delete_one(
+                doc! {
+                    "$and": [
+                        { "from": from },
+                        { "to": to }
+                    ]
+                },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_user_views(&self, user_id: MyUserId) -> DatabaseResult<Vec<View>> {
+        let cursor = self
+            .views_collection()
+            .find(doc! { "from": user_id }, None)
+            .await?;
+
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> DatabaseResult<Option<User>> {
+        Ok(self.users_collection()
+            .find_one(doc! { "username": username }, None)
+            .await?)
+    }
+
+    async fn count_users(&self) -> DatabaseResult<u64> {
+        Ok(self.users_collection()
+            .count_documents(None, None)
+            .await?)
+    }
+
+    async fn count_profiles(&self) -> DatabaseResult<u64> {
+        Ok(self.profiles_collection()
+            .count_documents(None, None)
+            .await?)
+    }
+
+    async fn count_likes_since(&self, since: mongodb::bson::DateTime) -> DatabaseResult<u64> {
+        Ok(self.views_collection()
+            .count_documents(doc! { "liked": true, "timestamp": { "$gte": since } }, None)
+            .await?)
+    }
+
+    async fn count_matches_since(&self, since: mongodb::bson::DateTime) -> DatabaseResult<u64> {
+        Ok(self.matches_collection()
+            .count_documents(doc! { "timestamp": { "$gte": since } }, None)
+            .await?)
+    }
+
+    async fn get_user(&self, id: MyUserId) -> DatabaseResult<Option<User>> {
+        let mut cursor = self
+            .users_collection()
+            .find(doc! { "id": id }, None)
+            .await?;
+
+        Ok(cursor.try_next().await?)
+    }
+
+    async fn get_profile(&self, id: MyUserId) -> DatabaseResult<Option<Profile>> {
+        let mut cursor = self
+            .profiles_collection()
+            .find(doc! { "id": id }, None)
+            .await?;
+
+        Ok(cursor.try_next().await?)
+    }
+
+    async fn add_report(&self, report: &Report) -> DatabaseResult<()> {
+        self.reports_collection()
+            .insert_one(report, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn count_reporters(&self, user_id: MyUserId) -> DatabaseResult<u64> {
+        Ok(self.reports_collection()
+            .distinct(
+                "from",
+                doc! { "to": user_id, "status": ReportStatus::Open },
+                None,
+            )
+            .await?
+            .len() as u64)
+    }
+
+    async fn resolve_reports(&self, user_id: MyUserId, status: ReportStatus) -> DatabaseResult<()> {
+        self.reports_collection()
+            .update_many(
+                doc! { "to": user_id, "status": ReportStatus::Open },
+                doc! { "$set": { "status": status } },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_reports_from_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Report>> {
+        let cursor = self
+            .reports_collection()
+            .find(doc! { "from": user_id }, None)
+            .await?;
+
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn add_block(&self, block: &Block) -> DatabaseResult<()> {
+        if self.is_blocked(*block.from(), *block.to()).await? {
+            return Ok(());
+        }
+
+        self.blocks_collection()
+            .insert_one(block, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn is_blocked(&self, first: MyUserId, second: MyUserId) -> DatabaseResult<bool> {
+        Ok(self.blocks_collection()
+            .find_one(
+                doc! {
+                    "$or": [
+                        { "from": first, "to": second },
+                        { "from": second, "to": first }
+                    ]
+                },
+                None,
+            )
+            .await?
+            .is_some())
+    }
+
+    async fn get_blocked_users(&self, user_id: MyUserId) -> DatabaseResult<Vec<MyUserId>> {
+        let blocks: Vec<Block> = self
+            .blocks_collection()
+            .find(doc! { "$or": [ { "from": user_id }, { "to": user_id } ] }, None)
+            .await?
+            .try_collect()
+            .await?;
+
+        Ok(blocks
+            .iter()
+            .map(|block| if *block.from() == user_id { *block.to() } else { *block.from() })
+            .collect())
+    }
+
+    async fn get_blocks_from_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Block>> {
+        let cursor = self
+            .blocks_collection()
+            .find(doc! { "from": user_id }, None)
+            .await?;
+
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn add_ban(&self, ban: &Ban) -> DatabaseResult<()> {
+        if !self.is_banned(*ban.id()).await? {
+            self.bans_collection()
+                .insert_one(ban, None)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn ban_user(&self, user_id: MyUserId) -> DatabaseResult<()> {
+        self.add_ban(&Ban::new(user_id)).await?;
+        self.set_profile_visibility(user_id, false).await
+    }
+
+    async fn unban_user(&self, user_id: MyUserId) -> DatabaseResult<()> {
+        self.bans_collection()
+            .delete_many(doc! { "id": user_id }, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn count_broadcast_recipients(&self, audience: &BroadcastAudience) -> DatabaseResult<u64> {
+        Ok(self.profiles_collection()
+            .count_documents(self.broadcast_filter(audience).await?, None)
+            .await?)
+    }
+
+    async fn get_broadcast_recipients(&self, audience: &BroadcastAudience) -> DatabaseResult<Vec<MyUserId>> {
+        Ok(self.profiles_collection()
+            .distinct("id", self.broadcast_filter(audience).await?, None)
+            .await?
+            .into_iter()
+            .filter_map(|id| mongodb::bson::from_bson(id).ok())
+            .collect())
+    }
+
+    async fn add_broadcast(&self, broadcast: &Broadcast) -> DatabaseResult<()> {
+        self.broadcasts_collection()
+            .insert_one(broadcast, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_broadcast(&self, broadcast: &Broadcast) -> DatabaseResult<()> {
+        self.broadcasts_collection()
+            .replace_one(doc! { "_id": broadcast.id() }, broadcast, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn is_banned(&self, user_id: MyUserId) -> DatabaseResult<bool> {
+        Ok(self.bans_collection()
+            .find_one(doc! { "id": user_id }, None)
+            .await?
+            .is_some())
+    }
+
+    async fn cache_location(&self, cached: &CachedLocation) -> DatabaseResult<()> {
+        self.location_cache_collection()
+            .replace_one(
+                doc! { "key": cached.key() },
+                cached,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_cached_location(&self, key: &str) -> DatabaseResult<Option<CachedLocation>> {
+        // The TTL monitor runs once a minute, so expired documents may still be there
+        Ok(self
+            .location_cache_collection()
+            .find_one(doc! { "key": key, "expires_at": { "$gt": mongodb::bson::DateTime::now() } }, None)
+            .await?)
+    }
+
+    async fn delete_user_data(&self, user_id: MyUserId) -> DatabaseResult<DeletionRecord> {
+        let users = self.users_collection()
+            .delete_many(doc! { "id": user_id }, None)
+            .await?
+            .deleted_count;
+
+        let profiles = self.profiles_collection()
+            .delete_many(doc! { "id": user_id }, None)
+            .await?
+            .deleted_count;
+
+        let likes = self.likes_collection()
+            .delete_many(doc! { "$or": [ { "from": user_id }, { "to": user_id } ] }, None)
+            .await?
+            .deleted_count;
+
+        let views = self.views_collection()
+            .delete_many(doc! { "$or": [ { "from": user_id }, { "to": user_id } ] }, None)
+            .await?
+            .deleted_count;
+
+        self.reports_collection()
+            .delete_many(doc! { "from": user_id }, None)
+            .await?;
+
+        self.blocks_collection()
+            .delete_many(doc! { "from": user_id }, None)
+            .await?;
+
+        self.matches_collection()
+            .delete_many(doc! { "$or": [ { "first": user_id }, { "second": user_id } ] }, None)
+            .await?;
+
+        let record = DeletionRecord::new(users, profiles, likes, views);
+
+        self.add_deletion(&record).await?;
+
+        log::info!(
+            "account deleted: {users} users, {profiles} profiles, {likes} likes, {views} views removed"
+        );
+
+        Ok(record)
+    }
+
+    async fn add_deletion(&self, record: &DeletionRecord) -> DatabaseResult<()> {
+        self.deletions_collection()
+            .insert_one(record, None)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// `IndexOptionsConflict`, the index exists with the same keys but other options
+fn is_index_options_conflict(error: &mongodb::error::Error) -> bool {
+    matches!(*error.kind, mongodb::error::ErrorKind::Command(ref error) if error.code == 85)
+}
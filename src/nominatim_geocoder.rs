@@ -0,0 +1,180 @@
+use crate::maps::{self, FetchingError, GeocodedCity, Geocoder};
+use crate::profile::Coordinates;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEFAULT_URL: &str = "https://nominatim.openstreetmap.org";
+pub const DEFAULT_USER_AGENT: &str = "deafbot";
+
+/// How many places are requested to find cities with the same name
+const MAX_CANDIDATES: usize = 5;
+
+/// OpenStreetMap Nominatim geocoder, doesn't need a key but requires an identifying User-Agent
+pub struct NominatimGeocoder {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl NominatimGeocoder {
+    pub fn new(url: String, user_agent: String, timeout: Duration) -> Arc<Self> {
+        let client = maps::http_client_builder(timeout)
+            .user_agent(user_agent)
+            .build()
+            .expect("Unable to build Nominatim HTTP client");
+
+        Arc::new(Self { client, url })
+    }
+
+    async fn fetch(&self, endpoint: &str, query: &[(&str, &str)]) -> Result<serde_json::Value, FetchingError> {
+        let response = self.client
+            .get(format!("{}/{endpoint}", self.url.trim_end_matches('/')))
+            .query(query)
+            .query(&[("format", "jsonv2"), ("addressdetails", "1"), ("accept-language", "ru")])
+            .send()
+            .await?;
+
+        maps::response_json(response).await
+    }
+
+    /// Picks the most specific settlement name from the `address` object
+    fn city_name_from_place(place: &serde_json::Value) -> Option<String> {
+        let address = &place["address"];
+
+        ["city", "town", "village", "municipality", "state"].into_iter()
+            .find_map(|key| address[key].as_str())
+            .map(|el| el.to_owned())
+    }
+
+    fn city_from_place(place: &serde_json::Value) -> Option<GeocodedCity> {
+        Some(GeocodedCity {
+            name: Self::city_name_from_place(place)?,
+            region: place["address"]["state"].as_str().map(|el| el.to_owned()),
+            coordinates: Self::coordinates_from_place(place),
+        })
+    }
+
+    /// Nominatim returns coordinates as strings
+    fn coordinates_from_place(place: &serde_json::Value) -> Option<Coordinates> {
+        let longitude = place["lon"].as_str()?.parse::<f64>().ok()?;
+        let latitude = place["lat"].as_str()?.parse::<f64>().ok()?;
+
+        Some(Coordinates::new(longitude, latitude))
+    }
+}
+
+#[async_trait]
+impl Geocoder for NominatimGeocoder {
+    fn name(&self) -> &'static str {
+        "nominatim"
+    }
+
+    async fn cities_by_name(&self, name: &str) -> Result<Vec<GeocodedCity>, FetchingError> {
+        let limit = MAX_CANDIDATES.to_string();
+        let places = self.fetch("search", &[("q", name), ("featureType", "settlement"), ("limit", &limit)]).await?;
+
+        let cities: Vec<_> = places.as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Self::city_from_place)
+            .collect();
+
+        if cities.is_empty() {
+            return Err(FetchingError::CityNotFound { name: name.to_owned() });
+        }
+
+        Ok(cities)
+    }
+
+    async fn city_by_coordinates(&self, coordinates: &Coordinates) -> Result<GeocodedCity, FetchingError> {
+        let latitude = coordinates.latitude().to_string();
+        let longitude = coordinates.longitude().to_string();
+
+        // zoom 10 is the city level
+        let place = self.fetch("reverse", &[("lat", &latitude), ("lon", &longitude), ("zoom", "10")]).await?;
+
+        Self::city_from_place(&place)
+            .ok_or_else(|| FetchingError::CityNotFound { name: format!("{longitude},{latitude}") })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub_server::{query, StubServer};
+
+    /// Recorded answer for `Кировск`, there are towns in two regions
+    const KIROVSK: &str = r#"[
+        {"place_id":1,"lat":"67.6153","lon":"33.6711","category":"place","type":"town","name":"Кировск","address":{"town":"Кировск","state":"Мурманская область","country":"Россия","country_code":"ru"}},
+        {"place_id":2,"lat":"59.8753","lon":"30.9813","category":"place","type":"town","name":"Кировск","address":{"town":"Кировск","county":"Кировский район","state":"Ленинградская область","country":"Россия","country_code":"ru"}},
+        {"place_id":3,"lat":"59.9","lon":"31.0","category":"highway","type":"residential","name":"Кировская улица","address":{"road":"Кировская улица","country":"Россия"}}
+    ]"#;
+
+    const KHIMKI: &str = r#"{"place_id":4,"lat":"55.8888","lon":"37.4303","category":"boundary","type":"administrative","name":"Химки","address":{"city":"Химки","county":"городской округ Химки","state":"Московская область","country":"Россия","country_code":"ru"}}"#;
+
+    fn geocoder(server: &StubServer) -> Arc<NominatimGeocoder> {
+        NominatimGeocoder::new(format!("{}/", server.url), "test".to_owned(), Duration::from_secs(5))
+    }
+
+    #[tokio::test]
+    async fn finds_cities_by_name() {
+        let server = StubServer::start(vec![(200, KIROVSK)]).await;
+
+        let cities = geocoder(&server).cities_by_name("Кировск").await.unwrap();
+
+        let labels: Vec<_> = cities.iter().map(GeocodedCity::label).collect();
+        assert_eq!(labels, ["Кировск, Мурманская область", "Кировск, Ленинградская область"]);
+        let coordinates = cities[1].coordinates.as_ref().unwrap();
+        assert_eq!((*coordinates.longitude(), *coordinates.latitude()), (30.9813, 59.8753));
+
+        let request = &server.requests()[0];
+        assert_eq!(request.path(), "/search");
+        assert_eq!(query(request, "q").as_deref(), Some("Кировск"));
+        assert_eq!(query(request, "format").as_deref(), Some("jsonv2"));
+        assert_eq!(query(request, "accept-language").as_deref(), Some("ru"));
+    }
+
+    #[tokio::test]
+    async fn finds_city_by_coordinates() {
+        let server = StubServer::start(vec![(200, KHIMKI)]).await;
+
+        let city = geocoder(&server).city_by_coordinates(&Coordinates::new(37.43, 55.9)).await.unwrap();
+
+        assert_eq!(city.label(), "Химки, Московская область");
+        let request = &server.requests()[0];
+        assert_eq!(request.path(), "/reverse");
+        assert_eq!(query(request, "lat").as_deref(), Some("55.9"));
+        assert_eq!(query(request, "lon").as_deref(), Some("37.43"));
+    }
+
+    #[tokio::test]
+    async fn reports_unknown_cities() {
+        let server = StubServer::start(vec![(200, "[]"), (200, r#"{"error":"Unable to geocode"}"#)]).await;
+        let geocoder = geocoder(&server);
+
+        assert!(matches!(
+            geocoder.cities_by_name("Абырвалг").await,
+            Err(FetchingError::CityNotFound { name }) if name == "Абырвалг"
+        ));
+        assert!(matches!(
+            geocoder.city_by_coordinates(&Coordinates::new(-30.0, 0.0)).await,
+            Err(FetchingError::CityNotFound { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn reports_rate_limits_and_server_errors() {
+        let server = StubServer::start(vec![(429, "{}"), (502, "{}")]).await;
+        let geocoder = geocoder(&server);
+
+        for expected in [429, 502] {
+            match geocoder.city_by_coordinates(&Coordinates::new(37.43, 55.9)).await {
+                Err(error @ FetchingError::UnsuccessfulRequest { response_code }) => {
+                    assert_eq!(response_code, expected);
+                    assert!(error.is_transient());
+                }
+                result => panic!("unexpected result {result:?}"),
+            }
+        }
+    }
+}
@@ -1,16 +1,26 @@
-use crate::{common, profile};
+use crate::{broadcast, common, profile};
+use crate::broadcast::{Broadcast, BroadcastAudience};
 use crate::common::{conversation};
-use crate::common::keyboard::{EditProfile, EnterProfileEditingMode, LookingAtProfiles, MakeKeyboard, Menu};
+use crate::common::keyboard::{
+    text, BlockMatch, CancelLikeMessage, ConfirmDeletion, EditPhotos, EditProfile, EnterProfileEditingMode,
+    LookingAtLikes, LookingAtProfiles, MakeKeyboard, Menu, ModerateReport, SearchSettings, SelectReportReason,
+    SetSearchAge, SetSearchDistance, SetSearchHearingLevel, SetSearchSex,
+};
 use crate::common::structs::SearchData;
 use crate::database::Database;
+use crate::maps::Maps;
 use crate::defines::{BotDialogue, TeloxideResult};
-use crate::profile::{Like, View};
-use crate::state::State;
+use crate::profile::{
+    Age, AgeFilter, Block, DistanceFilter, Like, LikeMessage, Match, Profile, ProfileBuildingState, Report, ReportReason,
+    ReportStatus, View,
+};
+use crate::state::{self, CityChoice, State};
 use crate::user::MyUserId;
 use std::sync::Arc;
 use teloxide::prelude::*;
-use teloxide::types::{MessageId, ParseMode};
-use crate::callback::ProfileField;
+use teloxide::types::{InputFile, KeyboardRemove, MessageId, ParseMode};
+use crate::application::Configuration;
+use crate::callback::{ProfileField, SearchOption};
 
 pub async fn start_looking_at_profiles(
     bot: Bot,
@@ -20,10 +30,14 @@ pub async fn start_looking_at_profiles(
 ) -> TeloxideResult {
     let user_id = user_id.into();
 
-    let suggestion = match common::next_suggestion(db.clone(), user_id).await {
+    let Some(user_profile) = db.get_profile(user_id).await? else {
+        return profile_not_found(bot, db, dialogue, user_id).await;
+    };
+
+    let suggestion = match common::next_suggestion(db.clone(), user_id).await? {
         Some(profile) => profile,
         None => {
-            conversation::send_no_suggestion(bot, user_id).await?;
+            conversation::send_no_suggestion(bot, db, user_id).await?;
             return Ok(());
         }
     };
@@ -35,7 +49,7 @@ pub async fn start_looking_at_profiles(
     dialogue
         .update(State::LookingAtProfiles {
             data: SearchData {
-                user_profile: db.get_profile(user_id).await.unwrap(),
+                user_profile,
                 profile_id: *suggestion.id(),
             },
         })
@@ -49,13 +63,18 @@ pub async fn start_looking_at_profiles(
 pub async fn start_looking_at_likes(
     bot: Bot,
     db: Arc<Database>,
+    config: Arc<Configuration>,
     dialogue: BotDialogue,
     user_id: impl Into<MyUserId>,
 ) -> TeloxideResult {
     let user_id = user_id.into();
 
-    let profile_who_have_liked = match common::next_profile_who_have_liked(db.clone(), user_id).await {
-        Some(profile) => profile,
+    let Some(user_profile) = db.get_profile(user_id).await? else {
+        return profile_not_found(bot, db, dialogue, user_id).await;
+    };
+
+    let (profile_who_have_liked, like) = match common::next_profile_who_have_liked(db.clone(), user_id).await? {
+        Some(found) => found,
         None => {
             bot.send_message(user_id, "🫥 Никто пока не лайкнул твою анкету")
                 .await?;
@@ -64,42 +83,60 @@ pub async fn start_looking_at_likes(
     };
 
     bot.send_message(user_id, "Смотрим, кто тебя лайкнул")
-        .reply_markup(LookingAtProfiles::keyboard())
+        .reply_markup(LookingAtLikes::keyboard())
         .await?;
 
     dialogue
         .update(State::LookingAtProfilesWhoHaveLiked {
             data: SearchData {
-                user_profile: db.get_profile(user_id).await.unwrap(),
+                user_profile,
                 profile_id: *profile_who_have_liked.id(),
             },
         })
         .await?;
 
-    conversation::send_profile(bot, user_id, &profile_who_have_liked).await?;
+    if let Some(message) = like.message() {
+        conversation::send_like_message(bot.clone(), user_id, message).await?;
+    }
+
+    conversation::send_profile(bot.clone(), user_id, &profile_who_have_liked).await?;
+    conversation::send_like_time_left(bot, user_id, &like, config.like_ttl()).await?;
 
     Ok(())
 }
 
+/// Returns _false_ if the users can't be matched anymore, the dialogue is reset then
 pub async fn match_likes(
     bot: Bot,
     db: Arc<Database>,
     dialogue: BotDialogue,
     user_id: impl Into<MyUserId>,
     data: &SearchData,
-) -> TeloxideResult {
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
     let user_id = user_id.into();
 
-    if db.get_like_to_user(user_id).await.is_none() {
-        bot.send_message(user_id, "Поздно, срок действия лайка уже истёк")
-            .reply_markup(Menu::keyboard())
+    let refusal = if db.is_blocked(user_id, data.profile_id).await? {
+        Some("🚫 Эта анкета больше недоступна")
+    } else if db.find_like(data.profile_id, user_id).await?.is_none() {
+        Some("Поздно, срок действия лайка уже истёк")
+    } else {
+        None
+    };
+
+    if let Some(refusal) = refusal {
+        bot.send_message(user_id, refusal)
+            .reply_markup(common::menu_keyboard(db, user_id).await?)
             .await?;
         dialogue.reset().await?;
-        return Ok(());
+        return Ok(false);
     }
 
-    let first_liked_user = db.get_user(data.profile_id).await.unwrap();
-    let dialogue_user = db.get_user(user_id).await.unwrap();
+    let (Some(first_liked_user), Some(dialogue_user)) = (db.get_user(data.profile_id).await?, db.get_user(user_id).await?) else {
+        profile_not_found(bot, db, dialogue, user_id).await?;
+        return Ok(false);
+    };
+
+    db.add_match(Match::new(data.profile_id, user_id)).await?;
 
     bot.clone()
         .parse_mode(ParseMode::Html)
@@ -111,6 +148,7 @@ pub async fn match_likes(
             ),
         )
         .disable_web_page_preview(true)
+        .reply_markup(BlockMatch::keyboard(data.profile_id))
         .await?;
 
     bot.clone()
@@ -129,9 +167,10 @@ pub async fn match_likes(
             ),
         )
         .disable_web_page_preview(true)
+        .reply_markup(BlockMatch::keyboard(user_id))
         .await?;
 
-    Ok(())
+    Ok(true)
 }
 
 #[inline]
@@ -140,11 +179,12 @@ pub async fn like_profile(
     db: Arc<Database>,
     user_id: impl Into<MyUserId>,
     data: &SearchData,
+    message: Option<LikeMessage>,
 ) -> TeloxideResult {
     let user_id = user_id.into();
 
-    db.add_like(Like::new(user_id, data.profile_id, None)).await;
-    db.add_view(View::new(user_id, data.profile_id, true)).await;
+    db.add_like(Like::new(user_id, data.profile_id, message)).await?;
+    db.add_view(View::new(user_id, data.profile_id, true)).await?;
 
     let _ = bot
         .parse_mode(ParseMode::Html)
@@ -154,6 +194,186 @@ pub async fn like_profile(
     Ok(())
 }
 
+pub async fn request_like_message(
+    bot: Bot,
+    dialogue: BotDialogue,
+    user_id: impl Into<MyUserId>,
+    data: SearchData,
+) -> TeloxideResult {
+    let user_id = user_id.into();
+
+    bot.send_message(
+        user_id,
+        format!(
+            "💌 Напиши короткое сообщение до {} символов, или отправь стикер или кружок",
+            profile::MAX_LIKE_MESSAGE_LENGTH
+        ),
+    )
+        .reply_markup(CancelLikeMessage::keyboard())
+        .await?;
+
+    dialogue.update(State::WritingLikeMessage { data }).await?;
+
+    Ok(())
+}
+
+pub async fn request_report(bot: Bot, user_id: impl Into<MyUserId>, reported: MyUserId) -> TeloxideResult {
+    bot.send_message(user_id.into(), "⚠ Что не так с этой анкетой?")
+        .reply_markup(SelectReportReason::keyboard(reported))
+        .await?;
+    Ok(())
+}
+
+/// Records the report, hides the profile once enough distinct users reported it,
+/// forwards the report to moderators and moves on to the next profile
+pub async fn report_profile(
+    bot: Bot,
+    db: Arc<Database>,
+    config: Arc<Configuration>,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    reported: MyUserId,
+    reason: ReportReason,
+) -> TeloxideResult {
+    let user_id: MyUserId = q.from.id.into();
+    let message_id = q.message.as_ref().unwrap().id;
+
+    // The reported user comes from the callback data, so only the profile on the screen can be reported
+    let state = dialogue.get().await?;
+    let is_viewed = match &state {
+        Some(State::LookingAtProfiles { data }) | Some(State::LookingAtProfilesWhoHaveLiked { data }) => {
+            data.profile_id == reported
+        }
+        _ => false,
+    };
+
+    if !is_viewed {
+        bot.edit_message_text(q.from.id, message_id, "Эта анкета уже закрыта, пожаловаться можно только на текущую")
+            .await?;
+        return Ok(());
+    }
+
+    db.add_report(&Report::new(user_id, reported, reason)).await?;
+    db.add_view(View::new(user_id, reported, false)).await?;
+
+    bot.edit_message_text(q.from.id, message_id, "🛡 Спасибо, жалоба отправлена модераторам")
+        .await?;
+
+    let reporters = db.count_reporters(reported).await?;
+
+    if reporters >= config.report_threshold() {
+        log::info!("profile `{reported}` hidden after reports from {reporters} users");
+        db.set_profile_visibility(reported, false).await?;
+    }
+
+    if let Some(chat_id) = config.moderator_chat_id() {
+        if let Some(profile) = db.get_profile(reported).await? {
+            conversation::send_profile(bot.clone(), chat_id, &profile).await?;
+
+            bot.clone().parse_mode(ParseMode::Html)
+                .send_message(
+                    chat_id,
+                    format!(
+                        "⚠ Жалоба на <code>{reported}</code>: {}\n\
+                        Пожаловались пользователей: <b>{reporters}</b>",
+                        reason.description()
+                    ),
+                )
+                .reply_markup(ModerateReport::keyboard(reported))
+                .await?;
+        }
+    }
+
+    match state {
+        Some(State::LookingAtProfiles { data }) => {
+            send_new_suggestion(bot, db, dialogue, user_id, data).await?;
+        }
+        Some(State::LookingAtProfilesWhoHaveLiked { data }) => {
+            db.remove_like(reported, user_id).await?;
+            give_new_liked_profile(bot, db, config, dialogue, user_id, data).await?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+pub async fn cancel_report(bot: Bot, q: CallbackQuery) -> TeloxideResult {
+    bot.delete_message(q.from.id, q.message.as_ref().unwrap().id).await?;
+    Ok(())
+}
+
+/// Applies the moderator's decision to all open reports on the user
+pub async fn moderate_report(
+    bot: Bot,
+    db: Arc<Database>,
+    config: Arc<Configuration>,
+    q: CallbackQuery,
+    reported: MyUserId,
+    status: ReportStatus,
+) -> TeloxideResult {
+    let message = q.message.as_ref().unwrap();
+
+    if !config.is_moderator_chat(message.chat.id) {
+        return Ok(());
+    }
+
+    db.resolve_reports(reported, status).await?;
+
+    let decision = match status {
+        ReportStatus::Dismissed => "✅ Жалобы отклонены",
+        ReportStatus::Warned => {
+            let _ = bot
+                .send_message(reported, "⚠ На твою анкету поступили жалобы. Пожалуйста, соблюдай правила сообщества")
+                .await;
+            "⚠ Пользователь предупреждён"
+        }
+        ReportStatus::Banned => {
+            db.ban_user(reported).await?;
+            let _ = bot
+                .send_message(reported, "⛔ Твой аккаунт заблокирован модераторами")
+                .await;
+            "⛔ Пользователь забанен"
+        }
+        ReportStatus::Open => return Ok(()),
+    };
+
+    log::info!("reports on `{reported}` resolved as {status:?} by `{}`", q.from.id);
+
+    bot.edit_message_text(
+        message.chat.id,
+        message.id,
+        format!("{}\n\n{decision} — {}", message.text().unwrap_or_default(), q.from.full_name()),
+    )
+        .await?;
+
+    Ok(())
+}
+
+/// Blocks the user for good and forgets the likes between both of them
+pub async fn block_user(
+    db: Arc<Database>,
+    user_id: impl Into<MyUserId>,
+    blocked: MyUserId,
+) -> TeloxideResult {
+    let user_id = user_id.into();
+
+    db.add_block(&Block::new(user_id, blocked)).await?;
+    db.remove_like(user_id, blocked).await?;
+    db.remove_like(blocked, user_id).await?;
+
+    Ok(())
+}
+
+pub async fn block_match(bot: Bot, db: Arc<Database>, q: CallbackQuery, blocked: MyUserId) -> TeloxideResult {
+    block_user(db, q.from.id, blocked).await?;
+
+    bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, "🚫 Пользователь заблокирован")
+        .await?;
+
+    Ok(())
+}
+
 #[inline]
 pub async fn dislike_profile(
     db: Arc<Database>,
@@ -161,7 +381,7 @@ pub async fn dislike_profile(
     data: &SearchData,
 ) -> TeloxideResult {
     db.add_view(View::new(user_id.into(), data.profile_id, false))
-        .await;
+        .await?;
     Ok(())
 }
 
@@ -174,7 +394,7 @@ pub async fn send_new_suggestion(
 ) -> TeloxideResult {
     let user_id = user_id.into();
 
-    match common::next_suggestion(db, user_id).await {
+    match common::next_suggestion(db.clone(), user_id).await? {
         Some(new_suggestion) => {
             data.profile_id = *new_suggestion.id();
 
@@ -183,7 +403,7 @@ pub async fn send_new_suggestion(
             conversation::send_profile(bot, user_id, &new_suggestion).await?;
         }
         None => {
-            conversation::send_no_suggestion(bot, user_id).await?;
+            conversation::send_no_suggestion(bot, db, user_id).await?;
 
             dialogue.reset().await?;
         }
@@ -195,21 +415,27 @@ pub async fn send_new_suggestion(
 pub async fn give_new_liked_profile(
     bot: Bot,
     db: Arc<Database>,
+    config: Arc<Configuration>,
     dialogue: BotDialogue,
     user_id: impl Into<MyUserId>,
     mut data: SearchData,
 ) -> TeloxideResult {
     let user_id = user_id.into();
 
-    match common::next_profile_who_have_liked(db.clone(), user_id).await {
-        Some(liked_profile) => {
+    match common::next_profile_who_have_liked(db.clone(), user_id).await? {
+        Some((liked_profile, like)) => {
             data.profile_id = *liked_profile.id();
 
             dialogue
                 .update(State::LookingAtProfilesWhoHaveLiked { data })
                 .await?;
 
-            conversation::send_profile(bot, user_id, &liked_profile).await?;
+            if let Some(message) = like.message() {
+                conversation::send_like_message(bot.clone(), user_id, message).await?;
+            }
+
+            conversation::send_profile(bot.clone(), user_id, &liked_profile).await?;
+            conversation::send_like_time_left(bot, user_id, &like, config.like_ttl()).await?;
         }
         None => {
             conversation::send_likes_are_over_now_search(bot.clone(), user_id).await?;
@@ -227,7 +453,7 @@ pub async fn set_hearing_level(
     q: CallbackQuery,
     hearing_level: profile::HearingLevel
 ) -> TeloxideResult {
-    db.set_profile_hearing_level(q.from.id, hearing_level).await;
+    db.set_profile_hearing_level(q.from.id, hearing_level).await?;
     bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, common::text::PROFILE_EDIT_MODE)
         .await?;
     bot.edit_message_reply_markup(q.from.id, q.message.as_ref().unwrap().id)
@@ -241,7 +467,7 @@ pub async fn leave_empty_description(
     db: Arc<Database>,
     q: CallbackQuery,
 ) -> TeloxideResult {
-    db.set_profile_description(q.from.id, None).await;
+    db.set_profile_description(q.from.id, None).await?;
     bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, common::text::PROFILE_EDIT_MODE)
         .await?;
     bot.edit_message_reply_markup(q.from.id, q.message.as_ref().unwrap().id)
@@ -250,13 +476,187 @@ pub async fn leave_empty_description(
     Ok(())
 }
 
+pub async fn remove_video(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+) -> TeloxideResult {
+    db.set_profile_video(q.from.id, None).await?;
+    bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, common::text::PROFILE_EDIT_MODE)
+        .reply_markup(EditProfile::keyboard())
+        .await?;
+    dialogue.reset().await?;
+    Ok(())
+}
+
+pub async fn show_photo_editor(
+    bot: Bot,
+    config: Arc<Configuration>,
+    user_id: impl Into<MyUserId>,
+    message_id: MessageId,
+    count: usize,
+) -> TeloxideResult {
+    bot.edit_message_text(
+        user_id.into(),
+        message_id,
+        format!("🖼 Фотографии {count}/{}\n\nНомера соответствуют порядку в анкете", config.max_photos()),
+    )
+        .reply_markup(EditPhotos::keyboard(count, config.max_photos()))
+        .await?;
+    Ok(())
+}
+
+/// Waits for a photo that replaces the one at _replace_ or is added to the end
+pub async fn request_photo(
+    bot: Bot,
+    q: CallbackQuery,
+    dialogue: BotDialogue,
+    replace: Option<usize>,
+) -> TeloxideResult {
+    let message_id = q.message.as_ref().unwrap().id;
+
+    bot.edit_message_text(q.from.id, message_id, "🖼 Отправь новое фото").await?;
+    dialogue.update(State::EditingPhotos { replace, message_id }).await?;
+
+    Ok(())
+}
+
+pub async fn move_photo_up(
+    bot: Bot,
+    db: Arc<Database>,
+    config: Arc<Configuration>,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    index: usize,
+) -> TeloxideResult {
+    let Some(profile) = db.get_profile(q.from.id).await? else {
+        return profile_not_found(bot, db, dialogue, q.from.id).await;
+    };
+
+    let mut photos = profile.photos().clone();
+
+    if index > 0 && index < photos.len() {
+        photos.swap(index - 1, index);
+        db.set_profile_photos(q.from.id, &photos).await?;
+    }
+
+    show_photo_editor(bot, config, q.from.id, q.message.as_ref().unwrap().id, photos.len()).await
+}
+
+/// Deletes the photo unless it's the last one, a profile can't be left without photos
+pub async fn delete_photo(
+    bot: Bot,
+    db: Arc<Database>,
+    config: Arc<Configuration>,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    index: usize,
+) -> TeloxideResult {
+    let Some(profile) = db.get_profile(q.from.id).await? else {
+        return profile_not_found(bot, db, dialogue, q.from.id).await;
+    };
+
+    let mut photos = profile.photos().clone();
+
+    if photos.len() > 1 && index < photos.len() {
+        photos.remove(index);
+        db.set_profile_photos(q.from.id, &photos).await?;
+    }
+
+    show_photo_editor(bot, config, q.from.id, q.message.as_ref().unwrap().id, photos.len()).await
+}
+
+/// Deletes everything sent after the edit mode message up to `last_message_id` and shows the edit mode again
+pub async fn return_to_profile_edit_mode(
+    bot: Bot,
+    dialogue: BotDialogue,
+    chat_id: ChatId,
+    message_id: MessageId,
+    last_message_id: MessageId,
+) -> TeloxideResult {
+    for id in (message_id.0 + 1)..=(last_message_id.0) {
+        bot.delete_message(chat_id, MessageId(id)).await?;
+    }
+
+    bot.edit_message_text(
+        chat_id,
+        message_id,
+        common::text::PROFILE_EDIT_MODE
+    )
+        .await?;
+
+    bot.edit_message_reply_markup(
+        chat_id,
+        message_id,
+    )
+        .reply_markup(EditProfile::keyboard())
+        .await?;
+
+    dialogue.reset().await?;
+
+    Ok(())
+}
+
+/// The user chose one of the ambiguous city candidates
+pub async fn choose_city(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    index: usize,
+) -> TeloxideResult {
+    let choice_message_id = q.message.as_ref().unwrap().id;
+
+    // The keyboard may be left from an earlier choice
+    let Some(State::ChoosingCity { mut candidates, purpose, message_id }) = dialogue.get().await? else {
+        return Ok(());
+    };
+
+    if message_id != choice_message_id || index >= candidates.len() {
+        return Ok(());
+    }
+
+    let location = candidates.swap_remove(index);
+
+    match purpose {
+        CityChoice::CreatingProfile { mut profile_builder } => {
+            bot.edit_message_text(q.from.id, choice_message_id, format!("🏘 {}", location.displayed())).await?;
+
+            profile_builder.location = Some(location);
+            state::ask_sex(&bot, profile_builder.id).await?;
+
+            dialogue
+                .update(State::CreatingProfile { profile_builder: *profile_builder, state: ProfileBuildingState::Sex })
+                .await?;
+        }
+        CityChoice::EditingProfile { message_id } => {
+            db.set_profile_location(q.from.id, location).await?;
+            return_to_profile_edit_mode(bot, dialogue, q.from.id.into(), message_id, choice_message_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn back_to_profile_editing(bot: Bot, q: CallbackQuery) -> TeloxideResult {
+    bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, common::text::PROFILE_EDIT_MODE)
+        .reply_markup(EditProfile::keyboard())
+        .await?;
+    Ok(())
+}
+
 pub async fn finish_editing_profile(bot: Bot, db: Arc<Database>, dialogue: BotDialogue, q: CallbackQuery) -> TeloxideResult {
     bot.delete_message(q.from.id, q.message.as_ref().unwrap().id).await?;
     bot.delete_message(q.from.id, MessageId(q.message.as_ref().unwrap().id.0 - 1)).await?;
 
-    bot.send_message(q.from.id, "✨ Твоя новая анкета").await?;
+    let Some(profile) = db.get_profile(q.from.id).await? else {
+        return profile_not_found(bot, db, dialogue, q.from.id).await;
+    };
 
-    let profile = db.get_profile(q.from.id).await.unwrap();
+    bot.send_message(q.from.id, "✨ Твоя новая анкета")
+        .reply_markup(Menu::keyboard_for(*profile.settings().show_up_in_search()))
+        .await?;
 
     conversation::send_profile_with_keyboard_inline(bot, q.from.id, &profile, EnterProfileEditingMode::keyboard()).await?;
 
@@ -265,14 +665,280 @@ pub async fn finish_editing_profile(bot: Bot, db: Arc<Database>, dialogue: BotDi
     Ok(())
 }
 
+pub async fn enter_search_settings(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    user_id: impl Into<MyUserId>,
+) -> TeloxideResult {
+    let user_id = user_id.into();
+
+    let Some(profile) = db.get_profile(user_id).await? else {
+        return profile_not_found(bot, db, dialogue, user_id).await;
+    };
+
+    bot.send_message(user_id, search_settings_text(&profile))
+        .reply_markup(SearchSettings::keyboard())
+        .await?;
+
+    Ok(())
+}
+
+pub async fn show_search_settings(bot: Bot, db: Arc<Database>, dialogue: BotDialogue, q: CallbackQuery) -> TeloxideResult {
+    let Some(profile) = db.get_profile(q.from.id).await? else {
+        return profile_not_found(bot, db, dialogue, q.from.id).await;
+    };
+
+    bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, search_settings_text(&profile))
+        .reply_markup(SearchSettings::keyboard())
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_search_settings_handler(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    search_option: SearchOption,
+) -> TeloxideResult {
+    let message_id = q.message.as_ref().unwrap().id;
+
+    match search_option {
+        SearchOption::Age => {
+            bot.edit_message_text(q.from.id, message_id, "🎂 Выбери возраст тех, кого ищешь")
+                .reply_markup(SetSearchAge::keyboard())
+                .await?;
+        }
+        SearchOption::WantToMeet => {
+            bot.edit_message_text(q.from.id, message_id, "👫 Кого ты хочешь встретить?")
+                .reply_markup(SetSearchSex::keyboard())
+                .await?;
+        }
+        SearchOption::HearingLevel => {
+            let Some(profile) = db.get_profile(q.from.id).await? else {
+                return profile_not_found(bot, db, dialogue, q.from.id).await;
+            };
+            let selected = profile.settings().search_options().hearing_level().clone().unwrap_or_default();
+
+            bot.edit_message_text(q.from.id, message_id, "👂 Отметь подходящие уровни слуха")
+                .reply_markup(SetSearchHearingLevel::keyboard(&selected))
+                .await?;
+        }
+        SearchOption::Distance => {
+            bot.edit_message_text(q.from.id, message_id, "📍 Как далеко искать?")
+                .reply_markup(SetSearchDistance::keyboard())
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn set_search_age(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    range: Option<(Age, Age)>,
+) -> TeloxideResult {
+    let age_filter = range.map(|(lowest, greatest)| AgeFilter::new(lowest, greatest));
+    db.set_search_age(q.from.id, age_filter).await?;
+    show_search_settings(bot, db, dialogue, q).await
+}
+
+pub async fn set_search_sex(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    sex: Option<profile::Sex>,
+) -> TeloxideResult {
+    db.set_search_sex(q.from.id, sex).await?;
+    show_search_settings(bot, db, dialogue, q).await
+}
+
+/// Adds the hearing level to the acceptable ones or removes it if it's already there.
+///
+/// _None_ accepts any hearing level
+pub async fn toggle_search_hearing_level(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    hearing_level: Option<profile::HearingLevel>,
+) -> TeloxideResult {
+    let Some(profile) = db.get_profile(q.from.id).await? else {
+        return profile_not_found(bot, db, dialogue, q.from.id).await;
+    };
+
+    let selected = match hearing_level {
+        Some(hl) => {
+            let mut selected = profile.settings().search_options().hearing_level().clone().unwrap_or_default();
+            match selected.iter().position(|el| *el == hl) {
+                Some(index) => { selected.remove(index); }
+                None => selected.push(hl),
+            }
+            selected
+        }
+        None => vec![],
+    };
+
+    db.set_search_hearing_level(q.from.id, (!selected.is_empty()).then(|| selected.clone())).await?;
+
+    bot.edit_message_reply_markup(q.from.id, q.message.as_ref().unwrap().id)
+        .reply_markup(SetSearchHearingLevel::keyboard(&selected))
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_search_distance(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+    max_meters: Option<u64>,
+) -> TeloxideResult {
+    db.set_search_distance(q.from.id, max_meters.map(DistanceFilter::new)).await?;
+    show_search_settings(bot, db, dialogue, q).await
+}
+
+pub async fn finish_search_settings(bot: Bot, db: Arc<Database>, dialogue: BotDialogue, q: CallbackQuery) -> TeloxideResult {
+    let Some(profile) = db.get_profile(q.from.id).await? else {
+        return profile_not_found(bot, db, dialogue, q.from.id).await;
+    };
+
+    bot.edit_message_text(
+        q.from.id,
+        q.message.as_ref().unwrap().id,
+        format!("✨ Настройки поиска сохранены\n\n{}", profile.settings().search_options().to_summary()),
+    )
+        .await?;
+
+    Ok(())
+}
+
+fn search_settings_text(profile: &Profile) -> String {
+    format!("{}\n\n{}", text::SEARCH_SETTINGS, profile.settings().search_options().to_summary())
+}
+
+/// Hides the profile from search and likes or shows it again.
+///
+/// A profile hidden because of reports stays hidden until moderators look at them
+pub async fn set_profile_visibility(
+    bot: Bot,
+    db: Arc<Database>,
+    config: Arc<Configuration>,
+    user_id: impl Into<MyUserId>,
+    show_up_in_search: bool,
+) -> TeloxideResult {
+    let user_id = user_id.into();
+
+    let on_hold = common::is_on_hold(&db, &config, user_id).await?;
+
+    if show_up_in_search && on_hold {
+        bot.send_message(user_id, "🛡 На твою анкету поступили жалобы, она вернётся в поиск после проверки модераторами")
+            .reply_markup(Menu::keyboard_for(false))
+            .await?;
+        return Ok(());
+    }
+
+    db.set_profile_visibility(user_id, show_up_in_search).await?;
+
+    let reply = if show_up_in_search {
+        "▶ Твоя анкета снова видна в поиске"
+    } else {
+        "⏸ Твоя анкета скрыта из поиска и лайков. Вернуть её можно кнопкой в меню или командой /resume"
+    };
+
+    bot.send_message(user_id, reply)
+        .reply_markup(Menu::keyboard_for(show_up_in_search))
+        .await?;
+
+    Ok(())
+}
+
+pub async fn request_account_deletion(bot: Bot, user_id: impl Into<MyUserId>) -> TeloxideResult {
+    bot.parse_mode(ParseMode::Html)
+        .send_message(
+            user_id.into(),
+            "🗑 Удалить аккаунт?\n\n\
+            Анкета, лайки и история просмотров будут удалены <b>безвозвратно</b>",
+        )
+        .reply_markup(ConfirmDeletion::keyboard())
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_account(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+) -> TeloxideResult {
+    db.delete_user_data(q.from.id).await?;
+
+    // exiting fails when the dialogue has never been stored, resetting works either way
+    dialogue.reset().await?;
+
+    bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, "🗑 Все твои данные удалены")
+        .await?;
+
+    bot.send_message(q.from.id, "Чтобы начать заново, отправь /start")
+        .reply_markup(KeyboardRemove::new())
+        .await?;
+
+    Ok(())
+}
+
+pub async fn cancel_account_deletion(bot: Bot, q: CallbackQuery) -> TeloxideResult {
+    bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, "Удаление отменено")
+        .await?;
+    Ok(())
+}
+
+pub async fn send_user_data(bot: Bot, db: Arc<Database>, user_id: impl Into<MyUserId>) -> TeloxideResult {
+    let user_id = user_id.into();
+
+    let export = db.export_user_data(user_id).await?;
+    let json = serde_json::to_vec_pretty(&export)?;
+
+    bot.send_document(user_id, InputFile::memory(json).file_name("mydata.json"))
+        .caption("📦 Все данные, которые мы о тебе храним")
+        .await?;
+
+    Ok(())
+}
+
 #[inline]
 pub async fn enter_menu(
     bot: Bot,
+    db: Arc<Database>,
     dialogue: BotDialogue,
     user_id: impl Into<MyUserId>,
 ) -> TeloxideResult {
     dialogue.update(State::None).await?;
-    conversation::send_menu(bot, user_id.into()).await?;
+    conversation::send_menu(bot, db, user_id.into()).await?;
+    Ok(())
+}
+
+/// The profile was deleted while a keyboard for it was still shown, the user is sent to the menu
+pub async fn profile_not_found(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    user_id: impl Into<MyUserId>,
+) -> TeloxideResult {
+    let user_id = user_id.into();
+
+    dialogue.reset().await?;
+
+    bot.send_message(user_id, "🫥 Анкета не найдена")
+        .reply_markup(common::menu_keyboard(db, user_id).await?)
+        .await?;
+
     Ok(())
 }
 
@@ -296,6 +962,7 @@ pub async fn enter_profile_editing_mode(
 pub async fn set_profile_editing_handler(
     bot: Bot,
     db: Arc<Database>,
+    config: Arc<Configuration>,
     q: CallbackQuery,
     dialogue: BotDialogue,
     profile_field: ProfileField
@@ -304,44 +971,54 @@ pub async fn set_profile_editing_handler(
         ProfileField::Name => profile_edit_handler_setters::name(bot, q, dialogue, profile_field).await?,
         ProfileField::Age => profile_edit_handler_setters::age(bot, q, dialogue, profile_field).await?,
         ProfileField::City => profile_edit_handler_setters::city(bot, q, dialogue, profile_field).await?,
-        ProfileField::HearingLevel => profile_edit_handler_setters::hearing_level(bot, db, q).await?,
+        ProfileField::HearingLevel => profile_edit_handler_setters::hearing_level(bot, db, q, dialogue).await?,
         ProfileField::Description => profile_edit_handler_setters::description(bot, q, dialogue, profile_field).await?,
-        ProfileField::Photo => profile_edit_handler_setters::photo(bot, q, dialogue, profile_field).await?,
+        ProfileField::Video => profile_edit_handler_setters::video(bot, q, dialogue, profile_field).await?,
+        ProfileField::Photo => profile_edit_handler_setters::photo(bot, db, config, q, dialogue).await?,
     }
 
     Ok(())
 }
 
 pub(crate) mod profile_edit_handler_setters {
-    use crate::common::keyboard::{LeaveEmptyDescription, MakeKeyboardInline, SetHearingLevel};
+    use crate::common::keyboard::{
+        LeaveEmptyDescription, MakeKeyboardInline, RemoveVideo, RequestLocation, SetHearingLevel,
+    };
     use super::*;
 
     #[inline]
     pub(crate) async fn name(bot: Bot, q: CallbackQuery, dialogue: BotDialogue, profile_field: ProfileField) -> TeloxideResult {
         bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, "✒ Отправь своё имя").await?;
-        dialogue.update(State::EditingProfile { profile_field, callback_query: q }).await?;
+        dialogue.update(State::EditingProfile { profile_field, message_id: q.message.as_ref().unwrap().id }).await?;
         Ok(())
     }
 
     #[inline]
     pub(crate) async fn age(bot: Bot, q: CallbackQuery, dialogue: BotDialogue, profile_field: ProfileField) -> TeloxideResult {
         bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, "📏 Отправь свой возраст").await?;
-        dialogue.update(State::EditingProfile { profile_field, callback_query: q }).await?;
+        dialogue.update(State::EditingProfile { profile_field, message_id: q.message.as_ref().unwrap().id }).await?;
         Ok(())
     }
 
     #[inline]
     pub(crate) async fn city(bot: Bot, q: CallbackQuery, dialogue: BotDialogue, profile_field: ProfileField) -> TeloxideResult {
         bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, "🏘 Отправь свой город").await?;
-        dialogue.update(State::EditingProfile { profile_field, callback_query: q }).await?;
+        bot.send_message(q.from.id, "Или поделись местоположением 👇")
+            .reply_markup(RequestLocation::keyboard())
+            .await?;
+        dialogue.update(State::EditingProfile { profile_field, message_id: q.message.as_ref().unwrap().id }).await?;
         Ok(())
     }
 
     #[inline]
-    pub(crate) async fn hearing_level(bot: Bot, db: Arc<Database>, q: CallbackQuery) -> TeloxideResult {
+    pub(crate) async fn hearing_level(bot: Bot, db: Arc<Database>, q: CallbackQuery, dialogue: BotDialogue) -> TeloxideResult {
+        let Some(profile) = db.get_profile(q.from.id).await? else {
+            return profile_not_found(bot, db, dialogue, q.from.id).await;
+        };
+
         bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, "👂 Выбери свой уровень слуха").await?;
         bot.edit_message_reply_markup(q.from.id, q.message.as_ref().unwrap().id)
-            .reply_markup(SetHearingLevel::keyboard(*db.get_profile(q.from.id).await.unwrap().sex()))
+            .reply_markup(SetHearingLevel::keyboard(*profile.sex()))
             .await?;
         Ok(())
     }
@@ -352,14 +1029,244 @@ pub(crate) mod profile_edit_handler_setters {
         bot.edit_message_reply_markup(q.from.id, q.message.as_ref().unwrap().id)
             .reply_markup(LeaveEmptyDescription::keyboard_inline())
             .await?;
-        dialogue.update(State::EditingProfile { profile_field, callback_query: q }).await?;
+        dialogue.update(State::EditingProfile { profile_field, message_id: q.message.as_ref().unwrap().id }).await?;
         Ok(())
     }
 
     #[inline]
-    pub(crate) async fn photo(bot: Bot, q: CallbackQuery, dialogue: BotDialogue, profile_field: ProfileField) -> TeloxideResult {
-        bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, "🖼 Отправь своё новое фото").await?;
-        dialogue.update(State::EditingProfile { profile_field, callback_query: q }).await?;
+    pub(crate) async fn video(bot: Bot, q: CallbackQuery, dialogue: BotDialogue, profile_field: ProfileField) -> TeloxideResult {
+        bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, "🎬 Отправь видео или кружок на жестовом языке")
+            .reply_markup(RemoveVideo::keyboard_inline())
+            .await?;
+        dialogue.update(State::EditingProfile { profile_field, message_id: q.message.as_ref().unwrap().id }).await?;
         Ok(())
     }
+
+    #[inline]
+    pub(crate) async fn photo(
+        bot: Bot,
+        db: Arc<Database>,
+        config: Arc<Configuration>,
+        q: CallbackQuery,
+        dialogue: BotDialogue,
+    ) -> TeloxideResult {
+        let Some(profile) = db.get_profile(q.from.id).await? else {
+            return profile_not_found(bot, db, dialogue, q.from.id).await;
+        };
+
+        show_photo_editor(bot, config, q.from.id, q.message.as_ref().unwrap().id, profile.photos().len()).await
+    }
+}
+
+pub async fn send_stats(
+    bot: Bot,
+    db: Arc<Database>,
+    maps: Arc<Maps>,
+    config: Arc<Configuration>,
+    chat_id: ChatId,
+) -> TeloxideResult {
+    let offset = config.utc_offset();
+    let midnight = chrono::Utc::now().with_timezone(&offset).date_naive().and_time(chrono::NaiveTime::MIN);
+    let today = mongodb::bson::DateTime::from_millis((midnight - offset).and_utc().timestamp_millis());
+    let cache = maps.cache_stats();
+
+    bot.parse_mode(ParseMode::Html)
+        .send_message(
+            chat_id,
+            format!(
+                "📊 Статистика\n\n\
+                Пользователей: <b>{}</b>\n\
+                Анкет: <b>{}</b>\n\
+                Лайков сегодня (UTC{offset}): <b>{}</b>\n\
+                Взаимных лайков сегодня (UTC{offset}): <b>{}</b>\n\n\
+                Кэш городов с запуска:\n\
+                найдено в кэше: <b>{}</b>\n\
+                «не существует» из кэша: <b>{}</b>\n\
+                запросов к геокодерам: <b>{}</b>",
+                db.count_users().await?,
+                db.count_profiles().await?,
+                db.count_likes_since(today).await?,
+                db.count_matches_since(today).await?,
+                cache.hits,
+                cache.not_found_hits,
+                cache.misses,
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn admin_ban(bot: Bot, db: Arc<Database>, chat_id: ChatId, target: &str) -> TeloxideResult {
+    let Some(user_id) = find_admin_target(bot.clone(), db.clone(), chat_id, target).await? else {
+        return Ok(());
+    };
+
+    db.ban_user(user_id).await?;
+    log::info!("user `{user_id}` banned by admin `{chat_id}`");
+
+    bot.send_message(chat_id, format!("⛔ Пользователь {user_id} забанен")).await?;
+    Ok(())
+}
+
+pub async fn admin_unban(bot: Bot, db: Arc<Database>, chat_id: ChatId, target: &str) -> TeloxideResult {
+    let Some(user_id) = find_admin_target(bot.clone(), db.clone(), chat_id, target).await? else {
+        return Ok(());
+    };
+
+    db.unban_user(user_id).await?;
+    log::info!("user `{user_id}` unbanned by admin `{chat_id}`");
+
+    bot.send_message(chat_id, format!("✅ Пользователь {user_id} разбанен. Анкета остаётся скрытой до /resume"))
+        .await?;
+    Ok(())
+}
+
+pub async fn admin_show_profile(bot: Bot, db: Arc<Database>, chat_id: ChatId, target: &str) -> TeloxideResult {
+    let Some(user_id) = find_admin_target(bot.clone(), db.clone(), chat_id, target).await? else {
+        return Ok(());
+    };
+
+    let Some(profile) = db.get_profile(user_id).await? else {
+        bot.send_message(chat_id, format!("У пользователя {user_id} нет анкеты")).await?;
+        return Ok(());
+    };
+
+    conversation::send_profile(bot.clone(), chat_id, &profile).await?;
+
+    let username = db.get_user(user_id).await?.map(|user| user.username().clone()).unwrap_or_default();
+
+    bot.parse_mode(ParseMode::Html)
+        .send_message(
+            chat_id,
+            format!(
+                "ID: <code>{user_id}</code>\n\
+                Username: @{username}\n\
+                В поиске: <b>{}</b>\n\
+                Забанен: <b>{}</b>\n\
+                Открытых жалоб: <b>{}</b>",
+                yes_no(*profile.settings().show_up_in_search()),
+                yes_no(db.is_banned(user_id).await?),
+                db.count_reporters(user_id).await?,
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn admin_hide_profile(bot: Bot, db: Arc<Database>, chat_id: ChatId, target: &str) -> TeloxideResult {
+    let Some(user_id) = find_admin_target(bot.clone(), db.clone(), chat_id, target).await? else {
+        return Ok(());
+    };
+
+    db.set_profile_visibility(user_id, false).await?;
+    log::info!("profile `{user_id}` hidden by admin `{chat_id}`");
+
+    bot.send_message(chat_id, format!("🙈 Анкета {user_id} скрыта из поиска")).await?;
+    Ok(())
+}
+
+/// Resolves the ID or username given to an admin command, telling the admin if there is no such user
+async fn find_admin_target(
+    bot: Bot,
+    db: Arc<Database>,
+    chat_id: ChatId,
+    target: &str,
+) -> Result<Option<MyUserId>, Box<dyn std::error::Error + Send + Sync>> {
+    let user_id = db.find_user_id(target.trim()).await?;
+
+    if user_id.is_none() {
+        bot.send_message(chat_id, "Пользователь не найден. Укажи ID или username").await?;
+    }
+
+    Ok(user_id)
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "да" } else { "нет" }
+}
+
+pub async fn start_broadcast(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    chat_id: ChatId,
+    args: &str,
+) -> TeloxideResult {
+    let audience = match BroadcastAudience::parse(args) {
+        Ok(audience) => audience,
+        Err(error) => {
+            bot.send_message(
+                chat_id,
+                format!(
+                    "Не получилось разобрать аудиторию: {error}\n\n\
+                    Пример: /broadcast city=Москва hearing=CompletelyDeaf sex=Female age=18-30"
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "Аудитория: {}\nПолучателей: {}\n\nПришли сообщение для рассылки",
+            audience.to_summary(),
+            db.count_broadcast_recipients(&audience).await?
+        ),
+    )
+    .reply_markup(KeyboardRemove::new())
+    .await?;
+
+    dialogue.update(State::WritingBroadcast { audience }).await?;
+
+    Ok(())
+}
+
+/// Starts sending the previewed broadcast in the background
+pub async fn send_broadcast(
+    bot: Bot,
+    db: Arc<Database>,
+    config: Arc<Configuration>,
+    dialogue: BotDialogue,
+    q: CallbackQuery,
+) -> TeloxideResult {
+    let message = q.message.as_ref().unwrap();
+
+    if !config.is_admin(q.from.id) {
+        return Ok(());
+    }
+
+    let Some(State::ConfirmingBroadcast { audience, message_id }) = dialogue.get().await? else {
+        bot.edit_message_text(message.chat.id, message.id, "Рассылка уже отправлена или отменена")
+            .await?;
+        return Ok(());
+    };
+
+    dialogue.reset().await?;
+
+    let recipients = db.get_broadcast_recipients(&audience).await?;
+    let broadcast = Broadcast::new(q.from.id, audience, message.chat.id, message_id, recipients.len() as u64);
+
+    db.add_broadcast(&broadcast).await?;
+
+    bot.edit_message_text(
+        message.chat.id,
+        message.id,
+        format!("📣 Рассылка запущена, получателей: {}", recipients.len()),
+    )
+    .await?;
+
+    tokio::spawn(broadcast::run(bot, db, broadcast, recipients));
+
+    Ok(())
+}
+
+pub async fn cancel_broadcast(bot: Bot, dialogue: BotDialogue, q: CallbackQuery) -> TeloxideResult {
+    dialogue.reset().await?;
+    bot.edit_message_text(q.from.id, q.message.as_ref().unwrap().id, "Рассылка отменена")
+        .await?;
+    Ok(())
 }
@@ -9,13 +9,24 @@ use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
 use std::str::FromStr;
+use std::time::Duration;
 use teloxide::types::{InputFile, InputMedia, InputMediaPhoto, ParseMode};
 
 pub type Age = i64;
 
 pub const MAX_NAME_LENGTH: usize = 25;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Telegram doesn't allow more media in one album
+pub const MAX_PHOTOS: usize = 10;
+
+pub const MAX_LIKE_MESSAGE_LENGTH: usize = 300;
+
+/// Search radius given to every new profile
+pub const DEFAULT_MAX_DISTANCE_METERS: u64 = 30_000;
+
+const EARTH_RADIUS_METERS: f64 = 6_378_100.0;
+
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
 pub struct View {
     from: MyUserId,
     to: MyUserId,
@@ -51,7 +62,7 @@ impl Default for View {
     }
 }
 
-#[derive(Debug, Default, Clone, Sequence)]
+#[derive(Debug, Default, Clone, Sequence, Serialize, Deserialize)]
 pub enum ProfileBuildingState {
     #[default]
     Name,
@@ -61,10 +72,11 @@ pub enum ProfileBuildingState {
     MeetingPreferences,
     HearingLevel,
     Description,
+    Video,
     Photo,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProfileBuilder {
     pub id: MyUserId,
     pub photos: Vec<PhotoId>,
@@ -75,6 +87,7 @@ pub struct ProfileBuilder {
     pub hearing_level: Option<HearingLevel>,
     pub location: Option<Location>,
     pub description: Option<String>,
+    pub video: Option<ProfileVideo>,
 }
 
 impl ProfileBuilder {
@@ -88,10 +101,12 @@ impl ProfileBuilder {
             hearing_level: self.hearing_level.unwrap_or_default(),
             location: self.location.unwrap_or_default(),
             description: self.description,
+            video: self.video,
             settings: Settings {
                 show_up_in_search: true,
                 search_options: SearchOptions {
                     sex: self.want_to_meet,
+                    distance: Some(DistanceFilter::new(DEFAULT_MAX_DISTANCE_METERS)),
                     ..Default::default()
                 }
             },
@@ -110,6 +125,7 @@ pub struct Profile {
     hearing_level: HearingLevel,
     location: Location,
     description: Option<String>,
+    video: Option<ProfileVideo>,
     settings: Settings,
 }
 
@@ -121,19 +137,23 @@ impl Profile {
         }
     }
 
-    pub fn search_filter(&self, viewed_profiles: &[View]) -> Document {
+    pub fn search_filter(&self, viewed_profiles: &[View], blocked_users: &[MyUserId]) -> Document {
         let viewed_profiles: Vec<i64> = viewed_profiles.iter().map(|el| el.to.0 as i64).collect();
+        let blocked_users: Vec<i64> = blocked_users.iter().map(|el| el.0 as i64).collect();
 
         let mut options = vec![
             doc! { "id": { "$ne": self.id.0 as i64 } },
             doc! { "settings.show_up_in_search": true },
-            doc! { "location.actual": &self.location.actual },
+            self.location_filter(),
             doc! { "$nor": [ { "id": { "$in": viewed_profiles } } ] },
+            doc! { "id": { "$nin": blocked_users } },
             doc! {
                 "$or": [
                     { "settings.search_options.age": { "$exists": false } },
-                    { "settings.search_options.age.greatest": { "$gte": self.age } },
-                    { "settings.search_options.age.lowest": { "$lte": self.age } }
+                    {
+                        "settings.search_options.age.lowest": { "$lte": self.age },
+                        "settings.search_options.age.greatest": { "$gte": self.age }
+                    }
                 ]
             },
             doc! {
@@ -165,6 +185,72 @@ impl Profile {
         doc! { "$and": options }
     }
 
+    /// Matches profiles within the search radius when both sides have coordinates,
+    /// otherwise falls back to matching the city
+    fn location_filter(&self) -> Document {
+        let same_city = doc! { "location.actual": &self.location.actual };
+
+        let (distance, coordinates) = match (
+            self.settings().search_options().distance.as_ref(),
+            self.location().coordinates(),
+        ) {
+            (Some(distance), Some(coordinates)) => (distance, coordinates),
+            _ => return same_city,
+        };
+
+        doc! {
+            "$or": [
+                {
+                    "location.coordinates": {
+                        "$geoWithin": {
+                            "$centerSphere": [
+                                [ coordinates.longitude, coordinates.latitude ],
+                                distance.radians()
+                            ]
+                        }
+                    }
+                },
+                {
+                    "location.coordinates": { "$exists": false },
+                    "location.actual": &self.location.actual
+                }
+            ]
+        }
+    }
+
+    /// Tells if [Profile::search_filter] would find the candidate,
+    /// for backends which can't run MongoDB queries
+    pub fn matches_search(&self, candidate: &Profile, viewed_profiles: &[View], blocked_users: &[MyUserId]) -> bool {
+        let theirs = candidate.settings().search_options();
+        let ours = self.settings().search_options();
+
+        candidate.id != self.id
+            && candidate.settings.show_up_in_search
+            && self.matches_location(candidate)
+            && !viewed_profiles.iter().any(|view| view.to == candidate.id)
+            && !blocked_users.contains(&candidate.id)
+            && theirs.age.as_ref().is_none_or(|af| (af.lowest..=af.greatest).contains(&self.age))
+            && theirs.sex.is_none_or(|sex| sex == self.sex)
+            && theirs.hearing_level.as_ref().is_none_or(|hl| hl.contains(&self.hearing_level))
+            && ours.age.as_ref().is_none_or(|af| (af.lowest..=af.greatest).contains(&candidate.age))
+            && ours.sex.is_none_or(|sex| sex == candidate.sex)
+            && ours.hearing_level.as_ref().is_none_or(|hl| hl.contains(&candidate.hearing_level))
+    }
+
+    /// Same rules as [Profile::location_filter]
+    fn matches_location(&self, candidate: &Profile) -> bool {
+        let same_city = candidate.location.actual == self.location.actual;
+
+        match (
+            self.settings().search_options().distance.as_ref(),
+            self.location().coordinates(),
+            candidate.location().coordinates(),
+        ) {
+            (Some(distance), Some(ours), Some(theirs)) => ours.distance_to(theirs) <= distance.max_meters as f64,
+            _ => same_city,
+        }
+    }
+
     pub fn to_mediagroup(&self) -> Vec<InputMedia> {
         let mut media_group = Vec::with_capacity(self.photos().len());
 
@@ -213,14 +299,14 @@ pub struct Like {
     from: MyUserId,
     to: MyUserId,
     timestamp: mongodb::bson::DateTime,
-    message: Option<String>,
+    message: Option<LikeMessage>,
 }
 
 impl Like {
     pub fn new(
         from: impl Into<MyUserId>,
         to: impl Into<MyUserId>,
-        message: impl Into<Option<String>>,
+        message: impl Into<Option<LikeMessage>>,
     ) -> Self {
         Self {
             from: from.into(),
@@ -229,6 +315,36 @@ impl Like {
             message: message.into(),
         }
     }
+
+    /// How long the like stays valid before the database removes it
+    pub fn time_left(&self, ttl: Duration) -> Duration {
+        let expires_at = self.timestamp.timestamp_millis() + ttl.as_millis() as i64;
+        let left = expires_at - mongodb::bson::DateTime::now().timestamp_millis();
+
+        Duration::from_millis(left.max(0) as u64)
+    }
+}
+
+/// Message attached to a like, shown above the liker's profile
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum LikeMessage {
+    Text(String),
+    Sticker(String),
+    VideoNote(String),
+}
+
+impl LikeMessage {
+    pub fn from_message(msg: &teloxide::types::Message) -> Option<Self> {
+        if let Some(text) = msg.text() {
+            return Some(Self::Text(text.to_owned()));
+        }
+
+        if let Some(sticker) = msg.sticker() {
+            return Some(Self::Sticker(sticker.file.id.to_owned()));
+        }
+
+        msg.video_note().map(|note| Self::VideoNote(note.file.id.to_owned()))
+    }
 }
 
 impl From<Like> for Bson {
@@ -248,7 +364,134 @@ impl Default for Like {
     }
 }
 
-#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+/// Mutual like, kept for statistics
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
+pub struct Match {
+    first: MyUserId,
+    second: MyUserId,
+    timestamp: mongodb::bson::DateTime,
+}
+
+impl Match {
+    pub fn new(first: impl Into<MyUserId>, second: impl Into<MyUserId>) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            timestamp: mongodb::bson::DateTime::now(),
+        }
+    }
+}
+
+/// Hides both users from each other for good, no matter who has blocked whom
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
+pub struct Block {
+    from: MyUserId,
+    to: MyUserId,
+    timestamp: mongodb::bson::DateTime,
+}
+
+impl Block {
+    pub fn new(from: impl Into<MyUserId>, to: impl Into<MyUserId>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+            timestamp: mongodb::bson::DateTime::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
+pub struct Report {
+    from: MyUserId,
+    to: MyUserId,
+    reason: ReportReason,
+    status: ReportStatus,
+    timestamp: mongodb::bson::DateTime,
+}
+
+impl Report {
+    pub fn new(from: impl Into<MyUserId>, to: impl Into<MyUserId>, reason: ReportReason) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+            reason,
+            status: ReportStatus::Open,
+            timestamp: mongodb::bson::DateTime::now(),
+        }
+    }
+
+    pub fn set_status(&mut self, status: ReportStatus) {
+        self.status = status;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sequence, Serialize, Deserialize)]
+pub enum ReportReason {
+    Fake,
+    Abuse,
+    Underage,
+    Spam,
+    Other,
+}
+
+impl ReportReason {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Fake => "Fake",
+            Self::Abuse => "Abuse",
+            Self::Underage => "Underage",
+            Self::Spam => "Spam",
+            Self::Other => "Other",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Fake => text::REPORT_FAKE,
+            Self::Abuse => text::REPORT_ABUSE,
+            Self::Underage => text::REPORT_UNDERAGE,
+            Self::Spam => text::REPORT_SPAM,
+            Self::Other => text::REPORT_OTHER,
+        }
+    }
+}
+
+impl Display for ReportReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+impl FromStr for ReportReason {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Fake" => Ok(Self::Fake),
+            "Abuse" => Ok(Self::Abuse),
+            "Underage" => Ok(Self::Underage),
+            "Spam" => Ok(Self::Spam),
+            "Other" => Ok(Self::Other),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ReportStatus {
+    Open,
+    Dismissed,
+    Warned,
+    Banned,
+}
+
+impl From<ReportStatus> for Bson {
+    fn from(value: ReportStatus) -> Self {
+        mongodb::bson::to_bson(&value).unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub enum Sex {
     #[default]
     Male,
@@ -277,12 +520,15 @@ impl FromStr for Sex {
         match s {
             text::BOY => Ok(Self::Male),
             text::GIRL => Ok(Self::Female),
+
+            "Male" => Ok(Self::Male),
+            "Female" => Ok(Self::Female),
             _ => Err(()),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Sequence, Serialize, Deserialize)]
 pub enum HearingLevel {
     #[default]
     CompletelyDeaf,
@@ -300,6 +546,16 @@ impl Display for HearingLevel {
     }
 }
 
+impl HearingLevel {
+    pub fn to_plural_str(self) -> &'static str {
+        match self {
+            Self::CompletelyDeaf => text::DEAF_PLURAL,
+            Self::HearingImpaired => text::HEARING_IMPAIRED_PLURAL,
+            Self::Hearing => text::HEARING_PLURAL,
+        }
+    }
+}
+
 impl From<HearingLevel> for Bson {
     fn from(value: HearingLevel) -> Self {
         mongodb::bson::to_bson(&value).unwrap()
@@ -339,6 +595,43 @@ pub struct SearchOptions {
     hearing_level: Option<Vec<HearingLevel>>,
 }
 
+impl SearchOptions {
+    /// Human-readable description of the options for the search settings menu
+    pub fn to_summary(&self) -> String {
+        let age = match self.age.as_ref() {
+            Some(af) => format!("{}–{}", af.lowest, af.greatest),
+            None => String::from(text::ANY),
+        };
+
+        let sex = match self.sex {
+            Some(Sex::Male) => text::WANT_A_BOY,
+            Some(Sex::Female) => text::WANT_A_GIRL,
+            None => text::WHATEVER,
+        };
+
+        let hearing_level = match self.hearing_level.as_ref() {
+            Some(levels) if !levels.is_empty() => levels
+                .iter()
+                .map(|hl| hl.to_plural_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => String::from(text::ANY),
+        };
+
+        let distance = match self.distance.as_ref() {
+            Some(df) => format!("до {} км", df.max_meters / 1000),
+            None => String::from(text::ONLY_MY_CITY),
+        };
+
+        format!(
+            "🎂 Возраст: {age}\n\
+            👫 Кого ищу: {sex}\n\
+            👂 Уровень слуха: {hearing_level}\n\
+            📍 Расстояние: {distance}"
+        )
+    }
+}
+
 impl From<SearchOptions> for Bson {
     fn from(value: SearchOptions) -> Self {
         mongodb::bson::to_bson(&value).unwrap()
@@ -367,6 +660,32 @@ impl Location {
     }
 }
 
+/// Geocoding result kept in the location cache under a normalized [CachedLocation::key].
+///
+/// `location` is _None_ when there is no such city, so it isn't looked up again until it expires
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
+pub struct CachedLocation {
+    key: String,
+    location: Option<Location>,
+    expires_at: mongodb::bson::DateTime,
+}
+
+impl CachedLocation {
+    pub fn new(key: impl Into<String>, location: Option<Location>, ttl: Duration) -> Self {
+        let expires_at = mongodb::bson::DateTime::now().timestamp_millis() + ttl.as_millis() as i64;
+
+        Self {
+            key: key.into(),
+            location,
+            expires_at: mongodb::bson::DateTime::from_millis(expires_at),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= mongodb::bson::DateTime::now()
+    }
+}
+
 impl From<Location> for Bson {
     fn from(value: Location) -> Self {
         mongodb::bson::to_bson(&value).unwrap()
@@ -389,26 +708,137 @@ impl Default for Location {
     }
 }
 
+/// Stored as a GeoJSON point so `location.coordinates` can have a `2dsphere` index
 #[derive(Debug, Clone, PartialEq, Getters, Serialize, Deserialize)]
+#[serde(into = "GeoJsonPoint", try_from = "GeoJsonPoint")]
 pub struct Coordinates {
     longitude: f64,
     latitude: f64,
 }
 
+impl Coordinates {
+    pub fn new(longitude: f64, latitude: f64) -> Self {
+        Self { longitude, latitude }
+    }
+}
+
+impl Coordinates {
+    /// Great-circle distance on the same sphere `$centerSphere` queries use
+    pub fn distance_to(&self, other: &Coordinates) -> f64 {
+        let (lat_a, lat_b) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let delta_lat = lat_b - lat_a;
+        let delta_lon = (other.longitude - self.longitude).to_radians();
+
+        let a = (delta_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (delta_lon / 2.0).sin().powi(2);
+
+        2.0 * EARTH_RADIUS_METERS * a.sqrt().asin()
+    }
+}
+
+impl From<&teloxide::types::Location> for Coordinates {
+    fn from(value: &teloxide::types::Location) -> Self {
+        Self::new(value.longitude, value.latitude)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct GeoJsonPoint {
+    r#type: String,
+    coordinates: [f64; 2],
+}
+
+impl From<Coordinates> for GeoJsonPoint {
+    fn from(value: Coordinates) -> Self {
+        Self {
+            r#type: String::from("Point"),
+            coordinates: [value.longitude, value.latitude],
+        }
+    }
+}
+
+impl TryFrom<GeoJsonPoint> for Coordinates {
+    type Error = String;
+
+    fn try_from(value: GeoJsonPoint) -> Result<Self, Self::Error> {
+        match value.r#type.as_str() {
+            "Point" => Ok(Self::new(value.coordinates[0], value.coordinates[1])),
+            kind => Err(format!("unexpected GeoJSON type `{kind}`, expected `Point`")),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Default, Getters, Serialize, Deserialize)]
 pub struct AgeFilter {
     pub(crate) greatest: Age,
     pub(crate) lowest: Age,
 }
 
+impl AgeFilter {
+    pub fn new(lowest: Age, greatest: Age) -> Self {
+        Self { greatest, lowest }
+    }
+}
+
+impl From<AgeFilter> for Bson {
+    fn from(value: AgeFilter) -> Self {
+        mongodb::bson::to_bson(&value).unwrap()
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Default, Getters, Serialize, Deserialize)]
 pub struct DistanceFilter {
     max_meters: u64,
 }
 
+impl From<DistanceFilter> for Bson {
+    fn from(value: DistanceFilter) -> Self {
+        mongodb::bson::to_bson(&value).unwrap()
+    }
+}
+
+impl DistanceFilter {
+    pub fn new(max_meters: u64) -> Self {
+        Self { max_meters }
+    }
+
+    /// Radius in radians as expected by `$centerSphere`
+    fn radians(&self) -> f64 {
+        self.max_meters as f64 / EARTH_RADIUS_METERS
+    }
+}
+
+/// Short video introduction, usually someone signing
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum ProfileVideo {
+    Video(String),
+    VideoNote(String),
+}
+
+impl ProfileVideo {
+    pub fn from_message(msg: &teloxide::types::Message) -> Option<Self> {
+        if let Some(video) = msg.video() {
+            return Some(Self::Video(video.file.id.to_owned()));
+        }
+
+        msg.video_note().map(|note| Self::VideoNote(note.file.id.to_owned()))
+    }
+}
+
+impl From<ProfileVideo> for Bson {
+    fn from(value: ProfileVideo) -> Self {
+        mongodb::bson::to_bson(&value).unwrap()
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct PhotoId(pub String);
 
+impl From<PhotoId> for Bson {
+    fn from(value: PhotoId) -> Self {
+        Self::String(value.0)
+    }
+}
+
 pub fn validate_name(n: &str) -> Result<(), NameValidationError> {
     let length = n.len();
 
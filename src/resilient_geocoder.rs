@@ -0,0 +1,238 @@
+use crate::maps::{FetchingError, GeocodedCity, Geocoder};
+use crate::profile::Coordinates;
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How an online geocoder is retried and when it's given a rest
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Extra attempts after a transient failure
+    pub retries: u32,
+    /// Delay before the first retry, doubled before every next one
+    pub retry_delay: Duration,
+    /// Failed requests in a row after which the geocoder isn't asked until `cooldown` passes
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+#[derive(Default)]
+struct Breaker {
+    failures: u32,
+    open_until: Option<Instant>,
+    probing: bool,
+}
+
+/// Retries transient failures of the wrapped geocoder and stops asking it during an outage.
+///
+/// Once the cooldown passes a single request is let through, the geocoder is used again if it succeeds
+pub struct ResilientGeocoder {
+    inner: Arc<dyn Geocoder>,
+    policy: RetryPolicy,
+    breaker: Mutex<Breaker>,
+}
+
+impl ResilientGeocoder {
+    pub fn wrap(inner: Arc<dyn Geocoder>, policy: RetryPolicy) -> Arc<Self> {
+        Arc::new(Self {
+            inner,
+            policy,
+            breaker: Mutex::new(Breaker::default()),
+        })
+    }
+
+    async fn call<'a, T>(
+        &'a self,
+        request: impl Fn() -> BoxFuture<'a, Result<T, FetchingError>>,
+    ) -> Result<T, FetchingError> {
+        if self.is_open() {
+            return Err(FetchingError::GeocoderUnavailable { geocoder: self.inner.name() });
+        }
+
+        let mut delay = self.policy.retry_delay;
+        let mut attempt = 0;
+
+        let result = loop {
+            match request().await {
+                Err(error) if error.is_transient() && attempt < self.policy.retries => {
+                    log::debug!("geocoder `{}` failed, retrying in {delay:?}: {error}", self.inner.name());
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => break result,
+            }
+        };
+
+        match &result {
+            Err(error) if error.is_transient() => self.record_failure(),
+            _ => self.record_success(),
+        }
+
+        result
+    }
+
+    fn is_open(&self) -> bool {
+        let mut breaker = self.breaker.lock().unwrap();
+
+        match breaker.open_until {
+            Some(until) if Instant::now() < until => true,
+            Some(_) => {
+                // Half-open: let only this request through, the breaker stays open for the others until it succeeds.
+                // Another failure opens the breaker again, a probe that never finishes is retried after the cooldown
+                breaker.open_until = Some(Instant::now() + self.policy.cooldown);
+                breaker.failures = self.policy.failure_threshold.saturating_sub(1);
+                breaker.probing = true;
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn record_failure(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.failures += 1;
+        breaker.probing = false;
+
+        if breaker.failures >= self.policy.failure_threshold {
+            log::warn!(
+                "geocoder `{}` failed {} times in a row, not asking it for {:?}",
+                self.inner.name(),
+                breaker.failures,
+                self.policy.cooldown
+            );
+            breaker.open_until = Some(Instant::now() + self.policy.cooldown);
+        }
+    }
+
+    fn record_success(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+
+        if breaker.probing {
+            log::info!("geocoder `{}` is available again", self.inner.name());
+        }
+
+        breaker.open_until = None;
+        breaker.failures = 0;
+        breaker.probing = false;
+    }
+}
+
+#[async_trait]
+impl Geocoder for ResilientGeocoder {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn is_exhaustive(&self) -> bool {
+        self.inner.is_exhaustive()
+    }
+
+    fn canonical_name(&self, name: &str) -> Option<String> {
+        self.inner.canonical_name(name)
+    }
+
+    async fn cities_by_name(&self, name: &str) -> Result<Vec<GeocodedCity>, FetchingError> {
+        self.call(|| self.inner.cities_by_name(name)).await
+    }
+
+    async fn city_by_coordinates(&self, coordinates: &Coordinates) -> Result<GeocodedCity, FetchingError> {
+        self.call(|| self.inner.city_by_coordinates(coordinates)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use tokio::sync::Notify;
+
+    /// Fails with 503 until `healthy` is set, the successful requests wait for `release`
+    #[derive(Default)]
+    struct FlakyGeocoder {
+        healthy: AtomicBool,
+        requests: AtomicU32,
+        release: Notify,
+    }
+
+    #[async_trait]
+    impl Geocoder for FlakyGeocoder {
+        fn name(&self) -> &'static str {
+            "flaky"
+        }
+
+        async fn cities_by_name(&self, name: &str) -> Result<Vec<GeocodedCity>, FetchingError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+
+            if !self.healthy.load(Ordering::SeqCst) {
+                return Err(FetchingError::UnsuccessfulRequest { response_code: 503 });
+            }
+
+            self.release.notified().await;
+
+            Ok(vec![GeocodedCity { name: name.to_owned(), region: None, coordinates: None }])
+        }
+
+        async fn city_by_coordinates(&self, _: &Coordinates) -> Result<GeocodedCity, FetchingError> {
+            unimplemented!()
+        }
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            retries: 0,
+            retry_delay: Duration::ZERO,
+            failure_threshold: 2,
+            cooldown: Duration::from_millis(50),
+        }
+    }
+
+    #[tokio::test]
+    async fn opens_after_repeated_failures() {
+        let inner = Arc::new(FlakyGeocoder::default());
+        let geocoder = ResilientGeocoder::wrap(inner.clone(), policy());
+
+        for _ in 0..2 {
+            let error = geocoder.cities_by_name("Омск").await.unwrap_err();
+            assert!(matches!(error, FetchingError::UnsuccessfulRequest { response_code: 503 }));
+        }
+
+        let error = geocoder.cities_by_name("Омск").await.unwrap_err();
+        assert!(matches!(error, FetchingError::GeocoderUnavailable { .. }));
+        assert_eq!(inner.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn lets_a_single_probe_through_after_cooldown() {
+        let inner = Arc::new(FlakyGeocoder::default());
+        let geocoder = ResilientGeocoder::wrap(inner.clone(), policy());
+
+        for _ in 0..2 {
+            geocoder.cities_by_name("Омск").await.unwrap_err();
+        }
+
+        inner.healthy.store(true, Ordering::SeqCst);
+        tokio::time::sleep(policy().cooldown).await;
+
+        let probe = tokio::spawn({
+            let geocoder = geocoder.clone();
+            async move { geocoder.cities_by_name("Омск").await }
+        });
+
+        while inner.requests.load(Ordering::SeqCst) < 3 {
+            tokio::task::yield_now().await;
+        }
+
+        let error = geocoder.cities_by_name("Омск").await.unwrap_err();
+        assert!(matches!(error, FetchingError::GeocoderUnavailable { .. }));
+
+        inner.release.notify_one();
+        probe.await.unwrap().unwrap();
+
+        let request = geocoder.cities_by_name("Омск");
+        inner.release.notify_one();
+        request.await.unwrap();
+        assert_eq!(inner.requests.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,978 @@
+use crate::broadcast::{Broadcast, BroadcastAudience};
+use crate::database::{self, DatabaseBackend, DatabaseResult};
+use crate::profile::{
+    AgeFilter, Block, CachedLocation, DistanceFilter, HearingLevel, Like, Location, Match, PhotoId, Profile, ProfileVideo,
+    Report, ReportStatus, Sex, View,
+};
+use crate::user::*;
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use mongodb::bson::Bson;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sqlx::sqlite::{Sqlite, SqliteConnectOptions, SqliteConnection, SqlitePool, SqlitePoolOptions};
+use sqlx::Executor;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often expired likes, views and cached locations are removed
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Keeps everything in a single SQLite file, meant for small deployments.
+///
+/// Every record is stored as a BSON document next to the columns it is looked up by.
+/// Searches narrow the profiles down in SQL and check the rest with [Profile::matches_search].
+/// Likes, views and cached locations are removed by the task started in [SqliteBackend::expire_after]
+pub struct SqliteBackend {
+    pool: SqlitePool,
+}
+
+/// A one-time change of the SQLite schema, recorded in the `migrations` table
+struct Migration {
+    version: i64,
+    description: &'static str,
+    sql: &'static str,
+}
+
+/// Every migration ever written, in the order they are applied.
+///
+/// Never change or remove an applied migration, add a new one instead
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "tables for users, profiles and their relations",
+        sql: "
+            CREATE TABLE users (id INTEGER PRIMARY KEY, username TEXT NOT NULL, data BLOB NOT NULL);
+            CREATE TABLE profiles (id INTEGER PRIMARY KEY, show_up_in_search INTEGER NOT NULL, data BLOB NOT NULL);
+            CREATE TABLE likes (from_id INTEGER NOT NULL, to_id INTEGER NOT NULL, timestamp INTEGER NOT NULL, data BLOB NOT NULL);
+            CREATE TABLE views (from_id INTEGER NOT NULL, to_id INTEGER NOT NULL, timestamp INTEGER NOT NULL, liked INTEGER NOT NULL, data BLOB NOT NULL);
+            CREATE TABLE matches (first INTEGER NOT NULL, second INTEGER NOT NULL, timestamp INTEGER NOT NULL, data BLOB NOT NULL);
+            CREATE TABLE reports (from_id INTEGER NOT NULL, to_id INTEGER NOT NULL, data BLOB NOT NULL);
+            CREATE TABLE blocks (from_id INTEGER NOT NULL, to_id INTEGER NOT NULL, data BLOB NOT NULL);
+            CREATE TABLE bans (id INTEGER PRIMARY KEY, data BLOB NOT NULL);
+            CREATE TABLE broadcasts (id TEXT PRIMARY KEY, data BLOB NOT NULL);
+            CREATE TABLE deletions (data BLOB NOT NULL);
+            CREATE TABLE location_cache (displayed TEXT NOT NULL, data BLOB NOT NULL);
+        ",
+    },
+    Migration {
+        version: 2,
+        description: "indexes for likes, views, reports, blocks, matches and the location cache",
+        sql: "
+            CREATE INDEX users_username ON users (username);
+            CREATE INDEX likes_to ON likes (to_id, timestamp);
+            CREATE INDEX likes_from ON likes (from_id, to_id);
+            CREATE INDEX views_from ON views (from_id, to_id);
+            CREATE INDEX views_timestamp ON views (timestamp);
+            CREATE INDEX matches_first ON matches (first);
+            CREATE INDEX matches_second ON matches (second);
+            CREATE INDEX reports_to ON reports (to_id);
+            CREATE INDEX reports_from ON reports (from_id);
+            CREATE INDEX blocks_from ON blocks (from_id, to_id);
+            CREATE INDEX blocks_to ON blocks (to_id);
+            CREATE INDEX location_cache_displayed ON location_cache (displayed);
+        ",
+    },
+    Migration {
+        version: 3,
+        description: "location cache keyed by normalized names, with expiry",
+        sql: "
+            DROP TABLE location_cache;
+            CREATE TABLE location_cache (key TEXT PRIMARY KEY, expires_at INTEGER NOT NULL, data BLOB NOT NULL);
+            CREATE INDEX location_cache_expires_at ON location_cache (expires_at);
+        ",
+    },
+    Migration {
+        version: 4,
+        description: "location cache without the coordinates of users who shared their location",
+        sql: "DELETE FROM location_cache;",
+    },
+    Migration {
+        version: 5,
+        description: "profile columns for search and broadcasts",
+        sql: "
+            ALTER TABLE profiles ADD COLUMN sex TEXT;
+            ALTER TABLE profiles ADD COLUMN age INTEGER;
+            ALTER TABLE profiles ADD COLUMN hearing_level TEXT;
+            ALTER TABLE profiles ADD COLUMN city TEXT;
+            ALTER TABLE profiles ADD COLUMN search_sex TEXT;
+            ALTER TABLE profiles ADD COLUMN search_age_lowest INTEGER;
+            ALTER TABLE profiles ADD COLUMN search_age_greatest INTEGER;
+            CREATE INDEX profiles_search ON profiles (show_up_in_search, sex, age);
+        ",
+    },
+];
+
+/// Visible profiles the user may be suggested, [Profile::matches_search] checks the location and the rest
+const SUGGESTION_CANDIDATES: &str = "
+    SELECT data FROM profiles
+    WHERE show_up_in_search = 1
+        AND id != ?1
+        AND id NOT IN (SELECT id FROM bans)
+        AND id NOT IN (SELECT value FROM json_each(?2))
+        AND (?3 IS NULL OR sex = ?3)
+        AND (?4 IS NULL OR age BETWEEN ?4 AND ?5)
+        AND (?6 IS NULL OR hearing_level IN (SELECT value FROM json_each(?6)))
+        AND (search_sex IS NULL OR search_sex = ?7)
+        AND (search_age_lowest IS NULL OR ?8 BETWEEN search_age_lowest AND search_age_greatest)
+";
+
+/// Same rules as [BroadcastAudience::matches], banned users never get a broadcast
+const BROADCAST_RECIPIENTS: &str = "
+    SELECT id FROM profiles
+    WHERE id NOT IN (SELECT id FROM bans)
+        AND (?1 IS NULL OR city = ?1)
+        AND (?2 IS NULL OR hearing_level = ?2)
+        AND (?3 IS NULL OR sex = ?3)
+        AND (?4 IS NULL OR age BETWEEN ?4 AND ?5)
+";
+
+impl SqliteBackend {
+    /// Opens the database file, creating it if it doesn't exist
+    pub async fn open(path: &str) -> Arc<Self> {
+        let options = SqliteConnectOptions::from_str(path)
+            .expect("Unable to parse sqlite storage path")
+            .create_if_missing(true);
+
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .expect("Unable to open sqlite storage");
+
+        Arc::new(Self { pool })
+    }
+
+    /// Applies the migrations missing from the `migrations` table
+    pub async fn migrate(&self) {
+        self.pool
+            .execute("CREATE TABLE IF NOT EXISTS migrations (version INTEGER PRIMARY KEY, description TEXT NOT NULL, applied INTEGER NOT NULL)")
+            .await
+            .unwrap();
+
+        let applied: Vec<i64> = sqlx::query_scalar("SELECT version FROM migrations")
+            .fetch_all(&self.pool)
+            .await
+            .unwrap();
+
+        for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+            log::info!("applying migration {}: {}", migration.version, migration.description);
+
+            let mut transaction = self.pool.begin().await.unwrap();
+
+            if let Err(error) = transaction.execute(migration.sql).await {
+                panic!("Unable to apply migration {}: {error}", migration.version);
+            }
+
+            sqlx::query("INSERT INTO migrations (version, description, applied) VALUES (?, ?, ?)")
+                .bind(migration.version)
+                .bind(migration.description)
+                .bind(mongodb::bson::DateTime::now().timestamp_millis())
+                .execute(&mut transaction)
+                .await
+                .unwrap();
+
+            transaction.commit().await.unwrap();
+        }
+
+        self.fill_search_columns().await.expect("Unable to fill profile search columns");
+    }
+
+    /// Profiles stored before migration 5 get their search columns from the stored document
+    async fn fill_search_columns(&self) -> DatabaseResult<()> {
+        let profiles: Vec<Profile> = self.select("SELECT data FROM profiles WHERE sex IS NULL", &[]).await?;
+
+        for profile in &profiles {
+            Self::put_profile(&self.pool, profile).await?;
+        }
+
+        if !profiles.is_empty() {
+            log::info!("search columns filled for {} profiles", profiles.len());
+        }
+
+        Ok(())
+    }
+
+    /// Starts removing likes and views once they are older than their lifetime, and expired cached locations,
+    /// the same way the TTL indexes of [crate::mongo_backend::MongoBackend] do
+    pub fn expire_after(self: &Arc<Self>, like_ttl: Duration, view_ttl: Duration) {
+        let backend = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(EXPIRY_INTERVAL);
+
+            loop {
+                interval.tick().await;
+
+                for (table, ttl) in [("likes", like_ttl), ("views", view_ttl)] {
+                    if let Err(error) = backend.remove_expired(table, ttl).await {
+                        log::error!("unable to remove expired `{table}`: {error}");
+                    }
+                }
+
+                if let Err(error) = backend.remove_expired_locations().await {
+                    log::error!("unable to remove expired `location_cache`: {error}");
+                }
+            }
+        });
+
+        log::info!("likes expire after {} hours", like_ttl.as_secs() / 60 / 60);
+        log::info!("views expire after {} hours", view_ttl.as_secs() / 60 / 60);
+    }
+
+    async fn remove_expired(&self, table: &str, ttl: Duration) -> DatabaseResult<()> {
+        let expired_before = mongodb::bson::DateTime::now().timestamp_millis() - ttl.as_millis() as i64;
+
+        let removed = sqlx::query(&format!("DELETE FROM {table} WHERE timestamp < ?"))
+            .bind(expired_before)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        if removed > 0 {
+            log::trace!("{removed} expired records removed from `{table}`");
+        }
+
+        Ok(())
+    }
+
+    /// Cached locations carry their own expiry time
+    async fn remove_expired_locations(&self) -> DatabaseResult<()> {
+        sqlx::query("DELETE FROM location_cache WHERE expires_at <= ?")
+            .bind(mongodb::bson::DateTime::now().timestamp_millis())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Decodes the `data` column of every row returned by the query
+    async fn select<T: DeserializeOwned>(&self, sql: &str, params: &[i64]) -> DatabaseResult<Vec<T>> {
+        let mut query = sqlx::query_scalar::<_, Vec<u8>>(sql);
+
+        for param in params {
+            query = query.bind(*param);
+        }
+
+        query
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|data| decode(data))
+            .collect()
+    }
+
+    async fn select_one<T: DeserializeOwned>(&self, sql: &str, params: &[i64]) -> DatabaseResult<Option<T>> {
+        Ok(self.select(sql, params).await?.into_iter().next())
+    }
+
+    /// Runs the statement and returns the number of affected rows
+    async fn execute(&self, sql: &str, params: &[i64]) -> DatabaseResult<u64> {
+        let mut query = sqlx::query(sql);
+
+        for param in params {
+            query = query.bind(*param);
+        }
+
+        Ok(query.execute(&self.pool).await?.rows_affected())
+    }
+
+    async fn count(&self, sql: &str, params: &[i64]) -> DatabaseResult<u64> {
+        let mut query = sqlx::query_scalar::<_, i64>(sql);
+
+        for param in params {
+            query = query.bind(*param);
+        }
+
+        Ok(query.fetch_one(&self.pool).await? as u64)
+    }
+
+    async fn put_profile<'e>(executor: impl Executor<'e, Database = Sqlite>, profile: &Profile) -> DatabaseResult<()> {
+        let options = profile.settings().search_options();
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO profiles (
+                id, show_up_in_search, sex, age, hearing_level, city, search_sex, search_age_lowest, search_age_greatest, data
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(sql_id(*profile.id()))
+        .bind(*profile.settings().show_up_in_search())
+        .bind(variant_name(profile.sex())?)
+        .bind(*profile.age())
+        .bind(variant_name(profile.hearing_level())?)
+        .bind(profile.location().actual())
+        .bind(options.sex().as_ref().map(variant_name).transpose()?)
+        .bind(options.age().as_ref().map(|af| *af.lowest()))
+        .bind(options.age().as_ref().map(|af| *af.greatest()))
+        .bind(encode(profile)?)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Applies the same `$set`/`$unset` as the MongoDB backend to the stored document of the profile.
+    ///
+    /// The profile is read and written back under `BEGIN IMMEDIATE`, so concurrent updates wait for each other
+    /// instead of overwriting one another
+    async fn update_profile(&self, user_id: MyUserId, path: &str, value: Option<Bson>) -> DatabaseResult<()> {
+        let mut connection = self.pool.acquire().await?;
+
+        connection.execute("BEGIN IMMEDIATE").await?;
+
+        let result = Self::update_stored_profile(&mut connection, user_id, path, value).await;
+        let end = if result.is_ok() { "COMMIT" } else { "ROLLBACK" };
+
+        if let Err(error) = connection.execute(end).await {
+            connection.execute("ROLLBACK").await?;
+            return Err(error.into());
+        }
+
+        result
+    }
+
+    async fn update_stored_profile(
+        connection: &mut SqliteConnection,
+        user_id: MyUserId,
+        path: &str,
+        value: Option<Bson>,
+    ) -> DatabaseResult<()> {
+        let data: Option<Vec<u8>> = sqlx::query_scalar("SELECT data FROM profiles WHERE id = ?")
+            .bind(sql_id(user_id))
+            .fetch_optional(&mut *connection)
+            .await?;
+
+        let Some(data) = data else {
+            return Ok(());
+        };
+
+        Self::put_profile(connection, &database::update_profile(&decode(&data)?, path, value)?).await
+    }
+
+    async fn set_search_option(&self, user_id: MyUserId, key: &str, v: Option<Bson>) -> DatabaseResult<()> {
+        self.update_profile(user_id, &format!("settings.search_options.{key}"), v).await
+    }
+
+    /// Profiles who receive the broadcast, banned users never get it
+    async fn broadcast_recipients(&self, audience: &BroadcastAudience) -> DatabaseResult<Vec<MyUserId>> {
+        let ids: Vec<i64> = sqlx::query_scalar(BROADCAST_RECIPIENTS)
+            .bind(audience.city().as_deref())
+            .bind(audience.hearing_level().as_ref().map(variant_name).transpose()?)
+            .bind(audience.sex().as_ref().map(variant_name).transpose()?)
+            .bind(audience.age().as_ref().map(|af| *af.lowest()))
+            .bind(audience.age().as_ref().map(|af| *af.greatest()))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(ids.into_iter().map(|id| MyUserId(id as u64)).collect())
+    }
+}
+
+#[async_trait]
+impl DatabaseBackend for SqliteBackend {
+    async fn add_user(&self, user: &User) -> DatabaseResult<()> {
+        log::trace!("new record of the user with id `{}` created", user.id());
+
+        sqlx::query("INSERT INTO users (id, username, data) VALUES (?, ?, ?)")
+            .bind(sql_id(*user.id()))
+            .bind(user.username())
+            .bind(encode(user)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn add_profile(&self, profile: &Profile) -> DatabaseResult<()> {
+        log::trace!("new record of the profile with id `{}` created", profile.id());
+
+        Self::put_profile(&self.pool, profile).await
+    }
+
+    async fn find_suggestion(
+        &self,
+        profile: &Profile,
+        viewed_profiles: &[View],
+        blocked_users: &[MyUserId],
+    ) -> DatabaseResult<Option<Profile>> {
+        let options = profile.settings().search_options();
+        let excluded: Vec<i64> = viewed_profiles.iter()
+            .map(|view| sql_id(*view.to()))
+            .chain(blocked_users.iter().map(|id| sql_id(*id)))
+            .collect();
+        let hearing_levels = match options.hearing_level() {
+            Some(levels) => Some(serde_json::json!(levels.iter().map(variant_name).collect::<DatabaseResult<Vec<_>>>()?)),
+            None => None,
+        };
+
+        let mut candidates = sqlx::query_scalar::<_, Vec<u8>>(SUGGESTION_CANDIDATES)
+            .bind(sql_id(*profile.id()))
+            .bind(serde_json::json!(excluded).to_string())
+            .bind(options.sex().as_ref().map(variant_name).transpose()?)
+            .bind(options.age().as_ref().map(|af| *af.lowest()))
+            .bind(options.age().as_ref().map(|af| *af.greatest()))
+            .bind(hearing_levels.map(|levels| levels.to_string()))
+            .bind(variant_name(profile.sex())?)
+            .bind(*profile.age())
+            .fetch(&self.pool);
+
+        // The first candidate in the right place is enough, the rest are never decoded
+        while let Some(data) = candidates.try_next().await? {
+            let candidate: Profile = decode(&data)?;
+
+            if profile.matches_search(&candidate, viewed_profiles, blocked_users) {
+                return Ok(Some(candidate));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn set_profile_name(&self, user_id: MyUserId, v: &str) -> DatabaseResult<()> {
+        self.update_profile(user_id, "name", Some(Bson::from(v))).await
+    }
+
+    async fn set_profile_age(&self, user_id: MyUserId, v: i64) -> DatabaseResult<()> {
+        self.update_profile(user_id, "age", Some(Bson::from(v))).await
+    }
+
+    async fn set_profile_location(&self, user_id: MyUserId, v: Location) -> DatabaseResult<()> {
+        self.update_profile(user_id, "location", Some(Bson::from(v))).await
+    }
+
+    async fn set_profile_hearing_level(&self, user_id: MyUserId, v: HearingLevel) -> DatabaseResult<()> {
+        self.update_profile(user_id, "hearing_level", Some(Bson::from(v))).await
+    }
+
+    async fn set_profile_description(&self, user_id: MyUserId, v: Option<&str>) -> DatabaseResult<()> {
+        self.update_profile(user_id, "description", v.map(Bson::from)).await
+    }
+
+    async fn set_profile_video(&self, user_id: MyUserId, v: Option<ProfileVideo>) -> DatabaseResult<()> {
+        self.update_profile(user_id, "video", v.map(Bson::from)).await
+    }
+
+    async fn set_profile_photos(&self, user_id: MyUserId, pics: &[PhotoId]) -> DatabaseResult<()> {
+        self.update_profile(user_id, "photos", Some(Bson::from(pics))).await
+    }
+
+    async fn set_profile_visibility(&self, user_id: MyUserId, show_up_in_search: bool) -> DatabaseResult<()> {
+        self.update_profile(user_id, "settings.show_up_in_search", Some(Bson::from(show_up_in_search))).await
+    }
+
+    async fn set_search_age(&self, user_id: MyUserId, v: Option<AgeFilter>) -> DatabaseResult<()> {
+        self.set_search_option(user_id, "age", v.map(Bson::from)).await
+    }
+
+    async fn set_search_sex(&self, user_id: MyUserId, v: Option<Sex>) -> DatabaseResult<()> {
+        self.set_search_option(user_id, "sex", v.map(Bson::from)).await
+    }
+
+    async fn set_search_hearing_level(&self, user_id: MyUserId, v: Option<Vec<HearingLevel>>) -> DatabaseResult<()> {
+        self.set_search_option(user_id, "hearing_level", v.map(Bson::from)).await
+    }
+
+    async fn set_search_distance(&self, user_id: MyUserId, v: Option<DistanceFilter>) -> DatabaseResult<()> {
+        self.set_search_option(user_id, "distance", v.map(Bson::from)).await
+    }
+
+    async fn add_like(&self, like: Like) -> DatabaseResult<()> {
+        sqlx::query("INSERT INTO likes (from_id, to_id, timestamp, data) VALUES (?, ?, ?, ?)")
+            .bind(sql_id(*like.from()))
+            .bind(sql_id(*like.to()))
+            .bind(like.timestamp().timestamp_millis())
+            .bind(encode(&like)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn add_match(&self, value: Match) -> DatabaseResult<()> {
+        sqlx::query("INSERT INTO matches (first, second, timestamp, data) VALUES (?, ?, ?, ?)")
+            .bind(sql_id(*value.first()))
+            .bind(sql_id(*value.second()))
+            .bind(value.timestamp().timestamp_millis())
+            .bind(encode(&value)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_user_matches(&self, user_id: MyUserId) -> DatabaseResult<Vec<Match>> {
+        self.select(
+            "SELECT data FROM matches WHERE first = ?1 OR second = ?1",
+            &[sql_id(user_id)],
+        )
+        .await
+    }
+
+    async fn add_view(&self, view: View) -> DatabaseResult<()> {
+        sqlx::query("INSERT INTO views (from_id, to_id, timestamp, liked, data) VALUES (?, ?, ?, ?, ?)")
+            .bind(sql_id(*view.from()))
+            .bind(sql_id(*view.to()))
+            .bind(view.timestamp().timestamp_millis())
+            .bind(*view.liked())
+            .bind(encode(&view)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_likes_to_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Like>> {
+        self.select(
+            "SELECT data FROM likes WHERE to_id = ? ORDER BY timestamp",
+            &[sql_id(user_id)],
+        )
+        .await
+    }
+
+    async fn get_likes_from_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Like>> {
+        self.select(
+            "SELECT data FROM likes WHERE from_id = ? ORDER BY timestamp",
+            &[sql_id(user_id)],
+        )
+        .await
+    }
+
+    async fn find_like(&self, from: MyUserId, to: MyUserId) -> DatabaseResult<Option<Like>> {
+        self.select_one(
+            "SELECT data FROM likes WHERE from_id = ? AND to_id = ?",
+            &[sql_id(from), sql_id(to)],
+        )
+        .await
+    }
+
+    async fn remove_like(&self, from: MyUserId, to: MyUserId) -> DatabaseResult<()> {
+        self.execute(
+            "DELETE FROM likes WHERE rowid = (SELECT rowid FROM likes WHERE from_id = ? AND to_id = ? LIMIT 1)",
+            &[sql_id(from), sql_id(to)],
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_user_views(&self, user_id: MyUserId) -> DatabaseResult<Vec<View>> {
+        self.select("SELECT data FROM views WHERE from_id = ?", &[sql_id(user_id)]).await
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> DatabaseResult<Option<User>> {
+        Ok(sqlx::query_scalar::<_, Vec<u8>>("SELECT data FROM users WHERE username = ?")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|data| decode(&data))
+            .transpose()?)
+    }
+
+    async fn count_users(&self) -> DatabaseResult<u64> {
+        self.count("SELECT COUNT(*) FROM users", &[]).await
+    }
+
+    async fn count_profiles(&self) -> DatabaseResult<u64> {
+        self.count("SELECT COUNT(*) FROM profiles", &[]).await
+    }
+
+    async fn count_likes_since(&self, since: mongodb::bson::DateTime) -> DatabaseResult<u64> {
+        self.count(
+            "SELECT COUNT(*) FROM views WHERE liked = 1 AND timestamp >= ?",
+            &[since.timestamp_millis()],
+        )
+        .await
+    }
+
+    async fn count_matches_since(&self, since: mongodb::bson::DateTime) -> DatabaseResult<u64> {
+        self.count(
+            "SELECT COUNT(*) FROM matches WHERE timestamp >= ?",
+            &[since.timestamp_millis()],
+        )
+        .await
+    }
+
+    async fn get_user(&self, id: MyUserId) -> DatabaseResult<Option<User>> {
+        self.select_one("SELECT data FROM users WHERE id = ?", &[sql_id(id)]).await
+    }
+
+    async fn get_profile(&self, id: MyUserId) -> DatabaseResult<Option<Profile>> {
+        self.select_one("SELECT data FROM profiles WHERE id = ?", &[sql_id(id)]).await
+    }
+
+    async fn add_report(&self, report: &Report) -> DatabaseResult<()> {
+        sqlx::query("INSERT INTO reports (from_id, to_id, data) VALUES (?, ?, ?)")
+            .bind(sql_id(*report.from()))
+            .bind(sql_id(*report.to()))
+            .bind(encode(report)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn count_reporters(&self, user_id: MyUserId) -> DatabaseResult<u64> {
+        let reports: Vec<Report> = self
+            .select("SELECT data FROM reports WHERE to_id = ?", &[sql_id(user_id)])
+            .await?;
+
+        let mut reporters: Vec<MyUserId> = reports
+            .iter()
+            .filter(|report| *report.status() == ReportStatus::Open)
+            .map(|report| *report.from())
+            .collect();
+
+        reporters.sort_by_key(|id| id.0);
+        reporters.dedup();
+        Ok(reporters.len() as u64)
+    }
+
+    async fn resolve_reports(&self, user_id: MyUserId, status: ReportStatus) -> DatabaseResult<()> {
+        let rows: Vec<(i64, Vec<u8>)> = sqlx::query_as("SELECT rowid, data FROM reports WHERE to_id = ?")
+            .bind(sql_id(user_id))
+            .fetch_all(&self.pool)
+            .await?;
+
+        for (rowid, data) in rows {
+            let mut report: Report = decode(&data)?;
+
+            if *report.status() != ReportStatus::Open {
+                continue;
+            }
+
+            report.set_status(status);
+
+            sqlx::query("UPDATE reports SET data = ? WHERE rowid = ?")
+                .bind(encode(&report)?)
+                .bind(rowid)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_reports_from_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Report>> {
+        self.select("SELECT data FROM reports WHERE from_id = ?", &[sql_id(user_id)]).await
+    }
+
+    async fn add_block(&self, block: &Block) -> DatabaseResult<()> {
+        if self.is_blocked(*block.from(), *block.to()).await? {
+            return Ok(());
+        }
+
+        sqlx::query("INSERT INTO blocks (from_id, to_id, data) VALUES (?, ?, ?)")
+            .bind(sql_id(*block.from()))
+            .bind(sql_id(*block.to()))
+            .bind(encode(block)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn is_blocked(&self, first: MyUserId, second: MyUserId) -> DatabaseResult<bool> {
+        Ok(self.count(
+            "SELECT COUNT(*) FROM blocks WHERE (from_id = ?1 AND to_id = ?2) OR (from_id = ?2 AND to_id = ?1)",
+            &[sql_id(first), sql_id(second)],
+        )
+        .await?
+            > 0)
+    }
+
+    async fn get_blocked_users(&self, user_id: MyUserId) -> DatabaseResult<Vec<MyUserId>> {
+        let blocks: Vec<Block> = self
+            .select(
+                "SELECT data FROM blocks WHERE from_id = ?1 OR to_id = ?1",
+                &[sql_id(user_id)],
+            )
+            .await?;
+
+        Ok(blocks
+            .iter()
+            .map(|block| if *block.from() == user_id { *block.to() } else { *block.from() })
+            .collect())
+    }
+
+    async fn get_blocks_from_user(&self, user_id: MyUserId) -> DatabaseResult<Vec<Block>> {
+        self.select("SELECT data FROM blocks WHERE from_id = ?", &[sql_id(user_id)]).await
+    }
+
+    async fn add_ban(&self, ban: &Ban) -> DatabaseResult<()> {
+        sqlx::query("INSERT OR IGNORE INTO bans (id, data) VALUES (?, ?)")
+            .bind(sql_id(*ban.id()))
+            .bind(encode(ban)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn ban_user(&self, user_id: MyUserId) -> DatabaseResult<()> {
+        self.add_ban(&Ban::new(user_id)).await?;
+        self.set_profile_visibility(user_id, false).await
+    }
+
+    async fn unban_user(&self, user_id: MyUserId) -> DatabaseResult<()> {
+        self.execute("DELETE FROM bans WHERE id = ?", &[sql_id(user_id)]).await?;
+
+        Ok(())
+    }
+
+    async fn count_broadcast_recipients(&self, audience: &BroadcastAudience) -> DatabaseResult<u64> {
+        Ok(self.broadcast_recipients(audience).await?.len() as u64)
+    }
+
+    async fn get_broadcast_recipients(&self, audience: &BroadcastAudience) -> DatabaseResult<Vec<MyUserId>> {
+        self.broadcast_recipients(audience).await
+    }
+
+    async fn add_broadcast(&self, broadcast: &Broadcast) -> DatabaseResult<()> {
+        sqlx::query("INSERT INTO broadcasts (id, data) VALUES (?, ?)")
+            .bind(broadcast.id().to_hex())
+            .bind(encode(broadcast)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_broadcast(&self, broadcast: &Broadcast) -> DatabaseResult<()> {
+        sqlx::query("UPDATE broadcasts SET data = ? WHERE id = ?")
+            .bind(encode(broadcast)?)
+            .bind(broadcast.id().to_hex())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn is_banned(&self, user_id: MyUserId) -> DatabaseResult<bool> {
+        Ok(self.count("SELECT COUNT(*) FROM bans WHERE id = ?", &[sql_id(user_id)]).await? > 0)
+    }
+
+    async fn cache_location(&self, cached: &CachedLocation) -> DatabaseResult<()> {
+        sqlx::query("INSERT OR REPLACE INTO location_cache (key, expires_at, data) VALUES (?, ?, ?)")
+            .bind(cached.key())
+            .bind(cached.expires_at().timestamp_millis())
+            .bind(encode(cached)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_cached_location(&self, key: &str) -> DatabaseResult<Option<CachedLocation>> {
+        Ok(sqlx::query_scalar::<_, Vec<u8>>("SELECT data FROM location_cache WHERE key = ? AND expires_at > ?")
+            .bind(key)
+            .bind(mongodb::bson::DateTime::now().timestamp_millis())
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|data| decode(&data))
+            .transpose()?)
+    }
+
+    async fn delete_user_data(&self, user_id: MyUserId) -> DatabaseResult<DeletionRecord> {
+        let user_id = sql_id(user_id);
+
+        let users = self.execute("DELETE FROM users WHERE id = ?", &[user_id]).await?;
+        let profiles = self.execute("DELETE FROM profiles WHERE id = ?", &[user_id]).await?;
+        let likes = self.execute("DELETE FROM likes WHERE from_id = ?1 OR to_id = ?1", &[user_id]).await?;
+        let views = self.execute("DELETE FROM views WHERE from_id = ?1 OR to_id = ?1", &[user_id]).await?;
+
+        self.execute("DELETE FROM reports WHERE from_id = ?", &[user_id]).await?;
+        self.execute("DELETE FROM blocks WHERE from_id = ?", &[user_id]).await?;
+        self.execute("DELETE FROM matches WHERE first = ?1 OR second = ?1", &[user_id]).await?;
+
+        let record = DeletionRecord::new(users, profiles, likes, views);
+
+        self.add_deletion(&record).await?;
+
+        Ok(record)
+    }
+
+    async fn add_deletion(&self, record: &DeletionRecord) -> DatabaseResult<()> {
+        sqlx::query("INSERT INTO deletions (data) VALUES (?)")
+            .bind(encode(record)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// SQLite has no unsigned integers, Telegram IDs fit into `i64`
+fn sql_id(user_id: MyUserId) -> i64 {
+    user_id.0 as i64
+}
+
+fn encode<T: Serialize>(value: &T) -> DatabaseResult<Vec<u8>> {
+    Ok(mongodb::bson::to_vec(value)?)
+}
+
+fn decode<T: DeserializeOwned>(data: &[u8]) -> DatabaseResult<T> {
+    Ok(mongodb::bson::from_slice(data)?)
+}
+
+/// Enum variant as it is named in the stored documents, e.g. `Female`
+fn variant_name<T: Serialize>(value: &T) -> DatabaseResult<String> {
+    match mongodb::bson::to_bson(value)? {
+        Bson::String(name) => Ok(name),
+        other => Ok(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_backend::tests::{fixtures, Fixtures};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fresh database file in the temporary directory
+    async fn backend() -> Arc<SqliteBackend> {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "lizmash-test-{}-{}.sqlite",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let backend = SqliteBackend::open(path.to_str().unwrap()).await;
+        backend.migrate().await;
+        backend
+    }
+
+    fn profile(id: u64) -> Profile {
+        serde_json::from_value(json!({
+            "id": id,
+            "photos": ["photo"],
+            "name": format!("Профиль {id}"),
+            "age": 25,
+            "sex": "Female",
+            "hearing_level": "CompletelyDeaf",
+            "location": { "displayed": "Москва", "actual": "Москва" },
+            "settings": { "show_up_in_search": true, "search_options": {} },
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn concurrent_updates_of_a_profile_are_all_kept() {
+        let backend = backend().await;
+        backend.add_profile(&profile(1)).await.unwrap();
+
+        let updates = (0..20).map(|i| {
+            let backend = backend.clone();
+            tokio::spawn(async move {
+                match i {
+                    0 => backend.set_profile_visibility(MyUserId(1), false).await,
+                    1 => backend.set_profile_name(MyUserId(1), "Аня").await,
+                    i => backend.set_profile_age(MyUserId(1), 20 + i).await,
+                }
+            })
+        });
+
+        for update in updates.collect::<Vec<_>>() {
+            update.await.unwrap().unwrap();
+        }
+
+        let stored = backend.get_profile(MyUserId(1)).await.unwrap().unwrap();
+        assert!(!stored.settings().show_up_in_search());
+        assert_eq!(stored.name(), "Аня");
+    }
+
+    #[tokio::test]
+    async fn finds_the_same_suggestions_as_matches_search() {
+        let profiles = fixtures();
+        let backend = backend().await;
+        let mut fixtures = Fixtures(11);
+
+        for profile in &profiles {
+            backend.add_profile(profile).await.unwrap();
+        }
+
+        for searcher in &profiles {
+            let blocked: Vec<_> = profiles.iter()
+                .filter(|_| *fixtures.pick(&[false, false, false, false, true]))
+                .map(|blocked| *blocked.id())
+                .collect();
+            let mut expected: Vec<_> = profiles.iter()
+                .filter(|candidate| searcher.matches_search(candidate, &[], &blocked))
+                .map(|candidate| candidate.id().0)
+                .collect();
+
+            let mut viewed = vec![];
+            while let Some(suggestion) = backend.find_suggestion(searcher, &viewed, &blocked).await.unwrap() {
+                viewed.push(View::new(*searcher.id(), *suggestion.id(), false));
+            }
+
+            let mut suggested: Vec<_> = viewed.iter().map(|view| view.to().0).collect();
+            expected.sort_unstable();
+            suggested.sort_unstable();
+
+            assert_eq!(suggested, expected, "searcher {searcher:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn updates_keep_the_search_columns_in_sync() {
+        let backend = backend().await;
+        let (searcher, candidate) = (profile(1), profile(2));
+
+        backend.add_profile(&searcher).await.unwrap();
+        backend.add_profile(&candidate).await.unwrap();
+        assert!(backend.find_suggestion(&searcher, &[], &[]).await.unwrap().is_some());
+
+        backend.set_profile_age(MyUserId(2), 40).await.unwrap();
+        backend.set_search_age(MyUserId(1), Some(AgeFilter::new(18, 30))).await.unwrap();
+        let searcher = backend.get_profile(MyUserId(1)).await.unwrap().unwrap();
+
+        assert!(backend.find_suggestion(&searcher, &[], &[]).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn broadcasts_to_the_audience_except_banned_users() {
+        let profiles = fixtures();
+        let backend = backend().await;
+
+        for profile in &profiles {
+            backend.add_profile(profile).await.unwrap();
+        }
+        backend.ban_user(*profiles[0].id()).await.unwrap();
+
+        for args in ["", "city=Москва", "hearing=HearingImpaired sex=Female", "age=20-30", "city=Химки sex=Male age=18-24"] {
+            let audience = BroadcastAudience::parse(args).unwrap();
+            let expected: Vec<_> = profiles[1..].iter()
+                .filter(|profile| audience.matches(profile))
+                .map(|profile| *profile.id())
+                .collect();
+
+            assert_eq!(backend.get_broadcast_recipients(&audience).await.unwrap(), expected, "{args}");
+        }
+    }
+
+    #[tokio::test]
+    async fn fills_the_search_columns_of_old_profiles() {
+        let backend = backend().await;
+        let old = profile(2);
+
+        backend.add_profile(&profile(1)).await.unwrap();
+        sqlx::query("INSERT INTO profiles (id, show_up_in_search, data) VALUES (?, 1, ?)")
+            .bind(sql_id(*old.id()))
+            .bind(encode(&old).unwrap())
+            .execute(&backend.pool)
+            .await
+            .unwrap();
+
+        backend.migrate().await;
+
+        // The stored sex is what the filter looks at, an old row without it would never be found
+        backend.set_search_sex(MyUserId(1), Some(Sex::Female)).await.unwrap();
+        let searcher = backend.get_profile(MyUserId(1)).await.unwrap().unwrap();
+        let suggestion = backend.find_suggestion(&searcher, &[], &[]).await.unwrap();
+        assert_eq!(suggestion.map(|profile| *profile.id()), Some(*old.id()));
+    }
+}
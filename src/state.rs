@@ -6,7 +6,8 @@ use crate::database::Database;
 use crate::defines::{BotDialogue, TeloxideResult};
 use crate::maps::{FetchingError, Maps};
 use crate::profile::{
-    Age, HearingLevel, NameValidationError, PhotoId, ProfileBuilder, ProfileBuildingState, Sex,
+    Age, HearingLevel, LikeMessage, NameValidationError, PhotoId, ProfileBuilder,
+    ProfileBuildingState, ProfileVideo, Sex,
 };
 use crate::user::{User};
 use enum_iterator::Sequence;
@@ -16,7 +17,10 @@ use std::sync::Arc;
 use teloxide::prelude::*;
 use teloxide::types::{Location, MessageId, ParseMode, PhotoSize};
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use crate::application::Configuration;
+use crate::broadcast::BroadcastAudience;
 use crate::callback::ProfileField;
 
 type CreatingProfileResult = Result<ProfileBuilder, CreatingProfileError>;
@@ -40,9 +44,18 @@ pub enum CreatingProfileError {
         #[from]
         error: NameValidationError,
     },
+
+    #[error("unable to fetch location: {error:?}")]
+    UnableToFetchLocation {
+        #[from]
+        error: FetchingError,
+    },
+
+    #[error("several cities found, the user has to choose one")]
+    AmbiguousCity,
 }
 
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub enum State {
     #[default]
     None,
@@ -60,10 +73,45 @@ pub enum State {
         data: SearchData,
     },
 
+    WritingLikeMessage {
+        data: SearchData,
+    },
+
+    /// Admin is writing a message to be sent to the audience
+    WritingBroadcast {
+        audience: BroadcastAudience,
+    },
+
+    /// Admin is looking at the broadcast preview, `message_id` is the message to be copied
+    ConfirmingBroadcast {
+        audience: BroadcastAudience,
+        message_id: MessageId,
+    },
+
     EditingProfile {
         profile_field: ProfileField,
-        callback_query: CallbackQuery
+        message_id: MessageId,
+    },
+
+    /// Waiting for a photo to replace the one at `replace` or to be added if it's _None_
+    EditingPhotos {
+        replace: Option<usize>,
+        message_id: MessageId,
     },
+
+    /// The city name is ambiguous, `message_id` is the message with the candidates to choose from
+    ChoosingCity {
+        candidates: Vec<profile::Location>,
+        purpose: CityChoice,
+        message_id: MessageId,
+    },
+}
+
+/// What the city is chosen for, so the dialogue can go on after the choice
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum CityChoice {
+    CreatingProfile { profile_builder: Box<ProfileBuilder> },
+    EditingProfile { message_id: MessageId },
 }
 
 /// Acting on the current viewing profile.
@@ -76,31 +124,26 @@ pub async fn act_on_profile(
 ) -> TeloxideResult {
     match msg.text() {
         Some(text::LIKE) => {
-            match common::check_for_match(db.clone(), data.profile_id, msg.chat.id).await {
-                CheckForMatchResult::Match => {
-                    bot.send_message(msg.chat.id, "У вас взаимный лайк 👇")
-                        .await?;
-                    perform::match_likes(
-                        bot.clone(),
-                        db.clone(),
-                        dialogue.clone(),
-                        msg.chat.id,
-                        &data,
-                    )
-                    .await?;
-
-                    db.remove_like(data.profile_id, msg.chat.id).await;
-                }
-                CheckForMatchResult::DontMatch => {
-                    perform::like_profile(bot.clone(), db.clone(), msg.chat.id, &data).await?;
-                }
+            if !like_suggestion(bot.clone(), db.clone(), dialogue.clone(), msg.chat.id, &data, None).await? {
+                return Ok(());
             }
         }
+        Some(text::LIKE_WITH_MESSAGE) => {
+            perform::request_like_message(bot, dialogue, msg.chat.id, data).await?;
+            return Ok(());
+        }
+        Some(text::REPORT) => {
+            perform::request_report(bot, msg.chat.id, data.profile_id).await?;
+            return Ok(());
+        }
         Some(text::DISLIKE) => {
             perform::dislike_profile(db.clone(), msg.chat.id, &data).await?;
         }
+        Some(text::BLOCK) => {
+            perform::block_user(db.clone(), msg.chat.id, data.profile_id).await?;
+        }
         Some(text::MENU) => {
-            perform::enter_menu(bot, dialogue, msg.chat.id).await?;
+            perform::enter_menu(bot, db, dialogue, msg.chat.id).await?;
             return Ok(());
         }
         _ => {}
@@ -111,61 +154,264 @@ pub async fn act_on_profile(
     Ok(())
 }
 
+/// Catching the message an admin wants to broadcast and showing its preview
+pub async fn catch_broadcast(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    msg: Message,
+    audience: BroadcastAudience,
+) -> TeloxideResult {
+    bot.send_message(msg.chat.id, "Так сообщение увидят получатели:").await?;
+    bot.copy_message(msg.chat.id, msg.chat.id, msg.id).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "Аудитория: {}\nПолучателей: {}",
+            audience.to_summary(),
+            db.count_broadcast_recipients(&audience).await?
+        ),
+    )
+    .reply_markup(ConfirmBroadcast::keyboard())
+    .await?;
+
+    dialogue
+        .update(State::ConfirmingBroadcast { audience, message_id: msg.id })
+        .await?;
+
+    Ok(())
+}
+
+/// Catching a message the user wants to send together with a like
+pub async fn catch_like_message(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    msg: Message,
+    data: SearchData,
+) -> TeloxideResult {
+    if msg.text() == Some(text::CANCEL) {
+        bot.send_message(msg.chat.id, "Смотрим анкеты")
+            .reply_markup(LookingAtProfiles::keyboard())
+            .await?;
+        dialogue.update(State::LookingAtProfiles { data }).await?;
+        return Ok(());
+    }
+
+    let message = match LikeMessage::from_message(&msg) {
+        Some(LikeMessage::Text(text)) if text.chars().count() > profile::MAX_LIKE_MESSAGE_LENGTH => {
+            bot.send_message(
+                msg.chat.id,
+                format!("Сообщение слишком длинное, максимум {} символов", profile::MAX_LIKE_MESSAGE_LENGTH),
+            )
+            .await?;
+            return Ok(());
+        }
+        Some(message) => message,
+        None => {
+            bot.send_message(msg.chat.id, "Отправь текст, стикер или кружок").await?;
+            return Ok(());
+        }
+    };
+
+    if !like_suggestion(bot.clone(), db.clone(), dialogue.clone(), msg.chat.id, &data, Some(message)).await? {
+        return Ok(());
+    }
+
+    bot.send_message(msg.chat.id, "💌 Отправлено")
+        .reply_markup(LookingAtProfiles::keyboard())
+        .await?;
+
+    perform::send_new_suggestion(bot, db, dialogue, msg.chat.id, data).await?;
+
+    Ok(())
+}
+
+/// Likes the current suggestion, or matches if the suggested user has already liked back.
+///
+/// On a match the message is delivered right away instead of being stored.
+///
+/// Returns _false_ if the match was refused, the menu is shown then and no more suggestions should be sent
+async fn like_suggestion(
+    bot: Bot,
+    db: Arc<Database>,
+    dialogue: BotDialogue,
+    user_id: ChatId,
+    data: &SearchData,
+    message: Option<LikeMessage>,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    // Nothing is sent between blocked users, the profile is just skipped
+    if db.is_blocked(user_id, data.profile_id).await? {
+        perform::dislike_profile(db, user_id, data).await?;
+        return Ok(true);
+    }
+
+    match common::check_for_match(db.clone(), data.profile_id, user_id).await? {
+        CheckForMatchResult::Match => {
+            bot.send_message(user_id, "У вас взаимный лайк 👇")
+                .await?;
+            let matched = perform::match_likes(
+                bot.clone(),
+                db.clone(),
+                dialogue,
+                user_id,
+                data,
+            )
+            .await?;
+
+            if !matched {
+                return Ok(false);
+            }
+
+            if let Some(ref message) = message {
+                conversation::send_like_message(bot, data.profile_id, message).await?;
+            }
+
+            db.remove_like(data.profile_id, user_id).await?;
+        }
+        CheckForMatchResult::DontMatch => {
+            perform::like_profile(bot, db, user_id, data, message).await?;
+        }
+    }
+
+    Ok(true)
+}
+
 pub async fn edit_profile(
     bot: Bot,
     db: Arc<Database>,
     maps: Arc<Maps>,
     dialogue: BotDialogue,
     msg: Message,
-    (edit_kind, callback_query): (ProfileField, CallbackQuery),
+    (edit_kind, message_id): (ProfileField, MessageId),
 ) -> TeloxideResult {
     match (edit_kind, msg.text(), msg.photo()) {
-        (ProfileField::Name, Some(new_name), _) => db.set_profile_name(msg.chat.id, new_name).await,
+        (ProfileField::City, None, _) if msg.location().is_some() => {
+            let coordinates = msg.location().unwrap().into();
+            let location = match maps.get_city_by_coordinates(coordinates).await {
+                Ok(loc) => loc,
+                Err(FetchingError::CityNotFound { .. }) => {
+                    bot.send_message(msg.chat.id, "🕵🏻‍♂️ Рядом с этим местом нет города").await?;
+                    return Ok(())
+                }
+                Err(error) => {
+                    log::error!("unable to fetch location: {error}");
+                    return conversation::send_try_again_later(bot, msg.chat.id).await;
+                }
+            };
+            db.set_profile_location(msg.chat.id, location).await?;
+        }
+        (ProfileField::Name, Some(new_name), _) => db.set_profile_name(msg.chat.id, new_name).await?,
         (ProfileField::Age, Some(new_age), _) => {
-            if let Ok(age) = new_age.parse() { db.set_profile_age(msg.chat.id, age).await }
+            if let Ok(age) = new_age.parse() { db.set_profile_age(msg.chat.id, age).await? }
         },
         (ProfileField::City, Some(new_city), _) => {
-            let location = match maps.get_actual_city(new_city).await {
-                Ok(loc) => loc,
+            let location = match maps.find_cities(new_city).await {
+                Ok(mut candidates) if candidates.len() == 1 => candidates.remove(0),
+                Ok(candidates) => {
+                    let purpose = CityChoice::EditingProfile { message_id };
+                    return ask_to_choose_city(bot, dialogue, msg.chat.id, candidates, purpose).await;
+                }
                 Err(FetchingError::CityNotFound { name }) => {
                     bot.parse_mode(ParseMode::Html).send_message(msg.chat.id, format!("🕵🏻‍♂️ Города <b>{name}</b> не существует")).await?;
                     return Ok(())
                 }
                 Err(error) => {
-                    bot.send_message(msg.chat.id, "Неизвестная ошибка. Сообщите о ней разработчику: nomoreqwerty@tuta.io").await?;
-                    panic!("unknown error: {error}");
+                    log::error!("unable to fetch location: {error}");
+                    return conversation::send_try_again_later(bot, msg.chat.id).await;
                 }
             };
-            db.set_profile_location(msg.chat.id, location).await;
+            db.set_profile_location(msg.chat.id, location).await?;
         }
-        (ProfileField::Description, Some(new_description), _) => db.set_profile_description(msg.chat.id, new_description).await,
-        (ProfileField::Photo, _, Some(photos)) => {
-            let photos = [photos.last().unwrap().file.id.to_owned()];
-            db.set_profile_picture(msg.chat.id, &photos).await;
+        (ProfileField::Description, Some(new_description), _) => db.set_profile_description(msg.chat.id, new_description).await?,
+        (ProfileField::Video, _, _) => {
+            if let Some(video) = ProfileVideo::from_message(&msg) { db.set_profile_video(msg.chat.id, Some(video)).await? }
         }
         _ => {}
     }
 
-    for id in (msg.id.0)..(callback_query.message.as_ref().unwrap().id.0) {
-        bot.delete_message(msg.chat.id, MessageId(id)).await?;
-    }
-
-    bot.delete_message(msg.chat.id, msg.id).await?;
+    perform::return_to_profile_edit_mode(bot, dialogue, msg.chat.id, message_id, msg.id).await
+}
 
-    bot.edit_message_text(
-        callback_query.from.id,
-        callback_query.message.as_ref().unwrap().id,
-        common::text::PROFILE_EDIT_MODE
-    )
+/// Sends the candidates of an ambiguous city and waits for the user to choose one
+async fn ask_to_choose_city(
+    bot: Bot,
+    dialogue: BotDialogue,
+    chat_id: ChatId,
+    candidates: Vec<profile::Location>,
+    purpose: CityChoice,
+) -> TeloxideResult {
+    let message = bot.send_message(chat_id, "🗺 Нашлось несколько городов, выбери свой")
+        .reply_markup(ChooseCity::keyboard(&candidates))
         .await?;
 
-    bot.edit_message_reply_markup(
-        callback_query.from.id,
-        callback_query.message.as_ref().unwrap().id,
-    )
-        .reply_markup(EditProfile::keyboard())
+    dialogue
+        .update(State::ChoosingCity { candidates, purpose, message_id: message.id })
         .await?;
 
+    Ok(())
+}
+
+/// The user sent another city or location instead of choosing one of the candidates
+pub async fn retype_city(
+    bot: Bot,
+    db: Arc<Database>,
+    maps: Arc<Maps>,
+    config: Arc<Configuration>,
+    msg: Message,
+    dialogue: BotDialogue,
+    (_, purpose, _): (Vec<profile::Location>, CityChoice, MessageId),
+) -> TeloxideResult {
+    match purpose {
+        CityChoice::CreatingProfile { profile_builder } => {
+            let state = ProfileBuildingState::Location;
+            dialogue
+                .update(State::CreatingProfile { profile_builder: (*profile_builder).clone(), state: state.clone() })
+                .await?;
+            build_profile(bot, db, maps, config, msg, dialogue, (*profile_builder, state)).await
+        }
+        CityChoice::EditingProfile { message_id } => {
+            let profile_field = ProfileField::City;
+            dialogue
+                .update(State::EditingProfile { profile_field: profile_field.clone(), message_id })
+                .await?;
+            edit_profile(bot, db, maps, dialogue, msg, (profile_field, message_id)).await
+        }
+    }
+}
+
+pub async fn edit_photos(
+    bot: Bot,
+    db: Arc<Database>,
+    config: Arc<Configuration>,
+    dialogue: BotDialogue,
+    msg: Message,
+    (replace, message_id): (Option<usize>, MessageId),
+) -> TeloxideResult {
+    let photo = match msg.photo() {
+        Some(photos) => PhotoId(photos.last().unwrap().file.id.to_owned()),
+        None => return Ok(()),
+    };
+
+    let Some(profile) = db.get_profile(msg.chat.id).await? else {
+        return perform::profile_not_found(bot, db, dialogue, msg.chat.id).await;
+    };
+
+    let mut photos = profile.photos().clone();
+
+    match replace {
+        Some(index) if index < photos.len() => photos[index] = photo,
+        _ if photos.len() < config.max_photos() => photos.push(photo),
+        _ => {}
+    }
+
+    db.set_profile_photos(msg.chat.id, &photos).await?;
+
+    bot.delete_message(msg.chat.id, msg.id).await?;
+
+    perform::show_photo_editor(bot, config, msg.chat.id, message_id, photos.len()).await?;
+
     dialogue.reset().await?;
 
     Ok(())
@@ -174,33 +420,45 @@ pub async fn edit_profile(
 pub async fn look_at_likes(
     bot: Bot,
     db: Arc<Database>,
+    config: Arc<Configuration>,
     dialogue: BotDialogue,
     msg: Message,
     data: SearchData,
 ) -> TeloxideResult {
     match msg.text() {
         Some(text::LIKE) => {
-            perform::match_likes(
+            let matched = perform::match_likes(
                 bot.clone(),
                 db.clone(),
                 dialogue.clone(),
                 msg.chat.id,
                 &data,
             )
-            .await?
+            .await?;
+
+            if !matched {
+                return Ok(());
+            }
+        }
+        Some(text::REPORT) => {
+            perform::request_report(bot, msg.chat.id, data.profile_id).await?;
+            return Ok(());
         }
         Some(text::DISLIKE) => {
             perform::dislike_profile(db.clone(), msg.chat.id, &data).await?;
         }
+        Some(text::BLOCK) => {
+            perform::block_user(db.clone(), msg.chat.id, data.profile_id).await?;
+        }
         Some(text::MENU) => {
-            perform::enter_menu(bot, dialogue, msg.chat.id).await?;
+            perform::enter_menu(bot, db, dialogue, msg.chat.id).await?;
             return Ok(());
         }
         _ => {}
     }
 
-    db.remove_like(data.profile_id, msg.chat.id).await;
-    perform::give_new_liked_profile(bot, db, dialogue, msg.chat.id, data).await?;
+    db.remove_like(data.profile_id, msg.chat.id).await?;
+    perform::give_new_liked_profile(bot, db, config, dialogue, msg.chat.id, data).await?;
 
     Ok(())
 }
@@ -208,6 +466,7 @@ pub async fn look_at_likes(
 pub async fn handle_message(
     bot: Bot,
     db: Arc<Database>,
+    config: Arc<Configuration>,
     dialogue: BotDialogue,
     msg: Message,
 ) -> TeloxideResult {
@@ -215,7 +474,7 @@ pub async fn handle_message(
         Some(text::WATCH_PROFILES) => {
             perform::start_looking_at_profiles(bot, db, dialogue, msg.chat.id).await?
         }
-        Some(text::MY_PROFILE) => match db.get_profile(msg.chat.id).await {
+        Some(text::MY_PROFILE) => match db.get_profile(msg.chat.id).await? {
             Some(ref profile) => {
                 conversation::send_profile_with_keyboard_inline(
                     bot,
@@ -228,15 +487,25 @@ pub async fn handle_message(
             None => conversation::default_start(bot, msg.chat.id).await?,
         },
         Some(text::WHO_LIKES_ME) => {
-            perform::start_looking_at_likes(bot, db, dialogue, msg.chat.id).await?
+            perform::start_looking_at_likes(bot, db, config, dialogue, msg.chat.id).await?
         }
-        Some(text::MENU) | Some(text::LIKE) | Some(text::DISLIKE) => {
+        Some(text::SEARCH_SETTINGS) => {
+            perform::enter_search_settings(bot, db, dialogue, msg.chat.id).await?
+        }
+        Some(text::PAUSE) => {
+            perform::set_profile_visibility(bot, db, config, msg.chat.id, false).await?
+        }
+        Some(text::RESUME) => {
+            perform::set_profile_visibility(bot, db, config, msg.chat.id, true).await?
+        }
+        Some(text::MENU) | Some(text::LIKE) | Some(text::LIKE_WITH_MESSAGE) | Some(text::DISLIKE)
+        | Some(text::REPORT) | Some(text::BLOCK) => {
             bot.send_message(
                 msg.chat.id,
                 "🫠 По какой-то причине ваша предыдущая активность была утеряна",
             )
             .await?;
-            perform::enter_menu(bot, dialogue, msg.chat.id).await?;
+            perform::enter_menu(bot, db, dialogue, msg.chat.id).await?;
         }
         _ => {}
     }
@@ -247,6 +516,7 @@ pub async fn build_profile(
     bot: Bot,
     db: Arc<Database>,
     maps: Arc<Maps>,
+    config: Arc<Configuration>,
     msg: Message,
     dialogue: BotDialogue,
     data: (ProfileBuilder, ProfileBuildingState),
@@ -261,7 +531,7 @@ pub async fn build_profile(
             catch_age(bot, profile_builder, text).await
         }
         (ProfileBuildingState::Location, text, location, _) => {
-            catch_location(bot, profile_builder, maps, text, location).await
+            catch_location(bot, dialogue.clone(), profile_builder, maps, text, location).await
         }
         (ProfileBuildingState::Sex, Some(text), _, _) => {
             catch_sex(bot, profile_builder, text).await
@@ -275,12 +545,47 @@ pub async fn build_profile(
         (ProfileBuildingState::Description, Some(text), _, _) => {
             catch_description(bot, profile_builder, text).await
         }
+        (ProfileBuildingState::Video, text, _, _) => {
+            catch_video(bot, profile_builder, text, ProfileVideo::from_message(&msg)).await
+        }
+        (ProfileBuildingState::Photo, Some(text::DONE), _, _) if !profile_builder.photos.is_empty() => {
+            common::finish_profile_creation(
+                bot,
+                db,
+                config,
+                &User::new(msg.from().unwrap()),
+                profile_builder,
+            )
+            .await?;
+            dialogue.reset().await?;
+            return Ok(());
+        }
         (ProfileBuildingState::Photo, _, _, Some(photos)) => {
             profile_builder = catch_photo(profile_builder, photos).await.unwrap();
 
+            if profile_builder.photos.len() < config.max_photos() {
+                bot.send_message(
+                    profile_builder.id,
+                    format!(
+                        "🖼 Фото добавлено {}/{}. Отправь ещё или нажми «{}»",
+                        profile_builder.photos.len(),
+                        config.max_photos(),
+                        text::DONE,
+                    ),
+                )
+                .reply_markup(PhotosDone::keyboard())
+                .await?;
+
+                dialogue
+                    .update(State::CreatingProfile { profile_builder, state })
+                    .await?;
+                return Ok(());
+            }
+
             common::finish_profile_creation(
                 bot,
                 db,
+                config,
                 &User::new(msg.from().unwrap()),
                 profile_builder,
             )
@@ -349,7 +654,7 @@ pub async fn catch_age(
     };
 
     bot.send_message(profile_builder.id, "В каком городе ты живёшь?")
-        //.reply_markup(RequestLocation::keyboard())
+        .reply_markup(RequestLocation::keyboard())
         .await
         .unwrap();
 
@@ -359,25 +664,60 @@ pub async fn catch_age(
 #[inline]
 pub async fn catch_location(
     bot: Bot,
+    dialogue: BotDialogue,
     mut profile_builder: ProfileBuilder,
     maps: Arc<Maps>,
     text: Option<&str>,
     location: Option<&Location>,
 ) -> CreatingProfileResult {
-    profile_builder.location = match (text, location) {
-        (Some(string), None) => Some(maps.get_actual_city(string).await.unwrap()),
-        (None, Some(_loc)) => todo!(),
-        (_, _) => unreachable!(),
+    let fetched = match (text, location) {
+        (Some(string), None) => maps.find_cities(string).await,
+        (None, Some(loc)) => maps.get_city_by_coordinates(loc.into()).await.map(|location| vec![location]),
+        (_, _) => {
+            return Err(CreatingProfileError::UnableToParseUserReply {
+                state: ProfileBuildingState::Location,
+                user_reply: text.unwrap_or_default().to_owned(),
+            })
+        }
     };
 
-    bot.send_message(profile_builder.id, "Ты парень или девушка?")
-        .reply_markup(SelectSex::keyboard())
-        .await
-        .unwrap();
+    profile_builder.location = match fetched {
+        Ok(mut candidates) if candidates.len() == 1 => candidates.pop(),
+        Ok(candidates) => {
+            let chat_id: ChatId = profile_builder.id.into();
+            let purpose = CityChoice::CreatingProfile { profile_builder: Box::new(profile_builder) };
+            ask_to_choose_city(bot, dialogue, chat_id, candidates, purpose).await?;
+            return Err(CreatingProfileError::AmbiguousCity);
+        }
+        Err(error @ FetchingError::CityNotFound { .. }) => {
+            bot.send_message(profile_builder.id, "🕵🏻‍♂️ Не удалось найти такой город, попробуй ещё раз")
+                .reply_markup(RequestLocation::keyboard())
+                .await
+                .unwrap();
+            return Err(error.into());
+        }
+        // The user stays on this step and can send the city again
+        Err(error) => {
+            log::error!("unable to fetch location: {error}");
+            conversation::send_try_again_later(bot, profile_builder.id.into()).await?;
+            return Err(error.into());
+        }
+    };
+
+    ask_sex(&bot, profile_builder.id).await?;
 
     Ok(profile_builder)
 }
 
+/// The step after the city
+pub(crate) async fn ask_sex(bot: &Bot, chat_id: impl Into<ChatId>) -> TeloxideResult {
+    bot.send_message(chat_id.into(), "Ты парень или девушка?")
+        .reply_markup(SelectSex::keyboard())
+        .await?;
+
+    Ok(())
+}
+
 #[inline]
 pub async fn catch_sex(
     bot: Bot,
@@ -462,7 +802,35 @@ pub async fn catch_description(
 
     bot.send_message(
         profile_builder.id,
-        "Отправь свою фотографию, чтобы мы знали, как ты выглядишь",
+        "🎬 Отправь короткое видео или кружок, где ты рассказываешь о себе на жестовом языке",
+    ).reply_markup(SkipVideo::keyboard())
+    .await
+    .unwrap();
+
+    Ok(profile_builder)
+}
+
+#[inline]
+pub async fn catch_video(
+    bot: Bot,
+    mut profile_builder: ProfileBuilder,
+    text: Option<&str>,
+    video: Option<ProfileVideo>,
+) -> CreatingProfileResult {
+    profile_builder.video = match (text, video) {
+        (Some(text::SKIP), _) => None,
+        (_, Some(video)) => Some(video),
+        (text, None) => {
+            return Err(CreatingProfileError::UnableToParseUserReply {
+                state: ProfileBuildingState::Video,
+                user_reply: text.unwrap_or_default().to_owned(),
+            })
+        }
+    };
+
+    bot.send_message(
+        profile_builder.id,
+        "Отправь свои фотографии, чтобы мы знали, как ты выглядишь. Можно несколько",
     ).reply_markup(teloxide::types::KeyboardRemove::new())
     .await
     .unwrap();
@@ -0,0 +1,63 @@
+use reqwest::Url;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Local HTTP server answering with recorded responses, one per request in the given order
+pub struct StubServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Url>>>,
+}
+
+impl StubServer {
+    /// Responses are `(status, JSON body)`, requests beyond them get 500
+    pub async fn start(responses: Vec<(u16, &'static str)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let base = url.clone();
+        let received = requests.clone();
+        tokio::spawn(async move {
+            let mut responses = responses.into_iter();
+
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                // Requests are GETs without a body, the head is all there is
+                let mut head = Vec::new();
+                let mut buffer = [0; 1024];
+                while !head.ends_with(b"\r\n\r\n") {
+                    match stream.read(&mut buffer).await.unwrap() {
+                        0 => break,
+                        read => head.extend_from_slice(&buffer[..read]),
+                    }
+                }
+
+                let head = String::from_utf8_lossy(&head);
+                let target = head.split_whitespace().nth(1).unwrap_or("/");
+                received.lock().unwrap().push(Url::parse(&format!("{base}{target}")).unwrap());
+
+                let (status, body) = responses.next().unwrap_or((500, "{}"));
+                let response = format!(
+                    "HTTP/1.1 {status} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len(),
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// Requests received so far
+    pub fn requests(&self) -> Vec<Url> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Value of the query parameter
+pub fn query(url: &Url, key: &str) -> Option<String> {
+    url.query_pairs().find(|(k, _)| k == key).map(|(_, value)| value.into_owned())
+}
@@ -1,5 +1,6 @@
 use chrono::Utc;
 use derive_getters::Getters;
+use crate::profile::{Block, Like, Match, Profile, Report, View};
 use mongodb::bson::Bson;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Debug, Display, Formatter};
@@ -84,3 +85,54 @@ impl Display for MyUserId {
         write!(f, "{}", self.0)
     }
 }
+
+/// Audit record of an account deletion. Holds only counts, no personal data
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
+pub struct DeletionRecord {
+    timestamp: mongodb::bson::DateTime,
+    users: u64,
+    profiles: u64,
+    likes: u64,
+    views: u64,
+}
+
+impl DeletionRecord {
+    pub fn new(users: u64, profiles: u64, likes: u64, views: u64) -> Self {
+        Self {
+            timestamp: mongodb::bson::DateTime::now(),
+            users,
+            profiles,
+            likes,
+            views,
+        }
+    }
+}
+
+/// Everything stored about a single user, sent back on request
+#[derive(Debug, Clone, Serialize)]
+pub struct UserDataExport {
+    pub user: Option<User>,
+    pub profile: Option<Profile>,
+    pub likes_sent: Vec<Like>,
+    pub likes_received: Vec<Like>,
+    pub views: Vec<View>,
+    pub reports_sent: Vec<Report>,
+    pub blocks_sent: Vec<Block>,
+    pub matches: Vec<Match>,
+}
+
+/// Banned users can't use the bot and are hidden from search
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
+pub struct Ban {
+    id: MyUserId,
+    timestamp: mongodb::bson::DateTime,
+}
+
+impl Ban {
+    pub fn new(id: impl Into<MyUserId>) -> Self {
+        Self {
+            id: id.into(),
+            timestamp: mongodb::bson::DateTime::now(),
+        }
+    }
+}
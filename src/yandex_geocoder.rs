@@ -0,0 +1,210 @@
+use crate::maps::{self, FetchingError, GeocodedCity, Geocoder};
+use crate::profile::Coordinates;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEFAULT_URL: &str = "https://geocode-maps.yandex.ru/1.x";
+
+/// How many objects are requested to find cities with the same name
+const MAX_CANDIDATES: usize = 5;
+
+/// Yandex Maps geocoder, needs an API key
+pub struct YandexGeocoder {
+    client: reqwest::Client,
+    url: String,
+    api_key: String,
+}
+
+impl YandexGeocoder {
+    pub fn new(url: String, api_key: String, timeout: Duration) -> Arc<Self> {
+        let client = maps::http_client_builder(timeout)
+            .build()
+            .expect("Unable to build Yandex HTTP client");
+
+        Arc::new(Self { client, url, api_key })
+    }
+
+    async fn fetch_geocode(&self, geocode: &str, results: usize, kind: Option<&str>) -> Result<serde_json::Value, FetchingError> {
+        let results = results.to_string();
+        let mut query = vec![
+            ("apikey", self.api_key.as_str()),
+            ("format", "json"),
+            ("results", results.as_str()),
+            ("geocode", geocode),
+        ];
+
+        if let Some(kind) = kind {
+            query.push(("kind", kind));
+        }
+
+        let response = self.client.get(&self.url).query(&query).send().await?;
+
+        maps::response_json(response).await
+    }
+
+    /// Found objects, the most relevant first
+    fn geo_objects(geocode_json: &serde_json::Value) -> impl Iterator<Item = &serde_json::Value> {
+        geocode_json["response"]["GeoObjectCollection"]["featureMember"].as_array()
+            .into_iter()
+            .flatten()
+            .map(|member| &member["GeoObject"])
+    }
+
+    fn city_from_geo_object(geo_object: &serde_json::Value) -> Option<GeocodedCity> {
+        let intersection = &geo_object["metaDataProperty"]["GeocoderMetaData"]["AddressDetails"]["Country"]
+            ["AdministrativeArea"];
+
+        Some(GeocodedCity {
+            name: Self::city_name_from_area(intersection)?,
+            region: intersection["AdministrativeAreaName"].as_str().map(|el| el.to_owned()),
+            coordinates: Self::coordinates_from_geo_object(geo_object),
+        })
+    }
+
+    /// Tries to get city name from an administrative area.
+    fn city_name_from_area(intersection: &serde_json::Value) -> Option<String> {
+        if let Some(city) =
+            intersection["SubAdministrativeArea"]["Locality"]["LocalityName"].as_str()
+        {
+            return Some(city.to_owned());
+        }
+
+        if let Some(city) = intersection["Locality"]["LocalityName"].as_str() {
+            return Some(city.to_owned());
+        }
+
+        intersection["AdministrativeAreaName"].as_str()
+            .map(|el| el.to_owned())
+    }
+
+    /// Yandex returns coordinates as `"longitude latitude"`
+    fn coordinates_from_geo_object(geo_object: &serde_json::Value) -> Option<Coordinates> {
+        let pos = geo_object["Point"]["pos"].as_str()?;
+
+        let mut split = pos.split_whitespace().map(|el| el.parse::<f64>());
+
+        match (split.next(), split.next()) {
+            (Some(Ok(longitude)), Some(Ok(latitude))) => Some(Coordinates::new(longitude, latitude)),
+            _ => None,
+        }
+    }
+}
+
+#[async_trait]
+impl Geocoder for YandexGeocoder {
+    fn name(&self) -> &'static str {
+        "yandex"
+    }
+
+    async fn cities_by_name(&self, name: &str) -> Result<Vec<GeocodedCity>, FetchingError> {
+        let geocode_json = self.fetch_geocode(name, MAX_CANDIDATES, None).await?;
+
+        // Besides the best match only other settlements are candidates, not streets named after the city
+        let cities: Vec<_> = Self::geo_objects(&geocode_json)
+            .enumerate()
+            .filter(|(i, geo_object)| *i == 0 || geo_object["metaDataProperty"]["GeocoderMetaData"]["kind"] == "locality")
+            .filter_map(|(_, geo_object)| Self::city_from_geo_object(geo_object))
+            .collect();
+
+        if cities.is_empty() {
+            return Err(FetchingError::CityNotFound { name: name.to_owned() });
+        }
+
+        Ok(cities)
+    }
+
+    async fn city_by_coordinates(&self, coordinates: &Coordinates) -> Result<GeocodedCity, FetchingError> {
+        let geocode = format!("{},{}", coordinates.longitude(), coordinates.latitude());
+        let geocode_json = self.fetch_geocode(&geocode, 1, Some("locality")).await?;
+
+        let city = Self::geo_objects(&geocode_json).next().and_then(Self::city_from_geo_object);
+
+        city.ok_or(FetchingError::CityNotFound { name: geocode })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub_server::{query, StubServer};
+
+    /// Recorded answer for `Тверь`, the street in Moscow is dropped as a candidate
+    const TVER: &str = r#"{"response":{"GeoObjectCollection":{"featureMember":[
+        {"GeoObject":{"name":"Тверь","metaDataProperty":{"GeocoderMetaData":{"kind":"locality","AddressDetails":{"Country":{"CountryName":"Россия","AdministrativeArea":{"AdministrativeAreaName":"Тверская область","Locality":{"LocalityName":"Тверь"}}}}}},"Point":{"pos":"35.911896 56.859611"}}},
+        {"GeoObject":{"name":"Тверская улица","metaDataProperty":{"GeocoderMetaData":{"kind":"street","AddressDetails":{"Country":{"CountryName":"Россия","AdministrativeArea":{"AdministrativeAreaName":"Москва","Locality":{"LocalityName":"Москва"}}}}}},"Point":{"pos":"37.607242 55.763995"}}},
+        {"GeoObject":{"name":"Тверь","metaDataProperty":{"GeocoderMetaData":{"kind":"locality","AddressDetails":{"Country":{"CountryName":"Россия","AdministrativeArea":{"AdministrativeAreaName":"Курская область","SubAdministrativeArea":{"SubAdministrativeAreaName":"Курский район","Locality":{"LocalityName":"Тверь"}}}}}}},"Point":{"pos":"36.1 51.7"}}}
+    ]}}}"#;
+
+    const KHIMKI: &str = r#"{"response":{"GeoObjectCollection":{"featureMember":[
+        {"GeoObject":{"name":"Химки","metaDataProperty":{"GeocoderMetaData":{"kind":"locality","AddressDetails":{"Country":{"CountryName":"Россия","AdministrativeArea":{"AdministrativeAreaName":"Московская область","SubAdministrativeArea":{"SubAdministrativeAreaName":"городской округ Химки","Locality":{"LocalityName":"Химки"}}}}}}},"Point":{"pos":"37.444779 55.888796"}}}
+    ]}}}"#;
+
+    const NOTHING: &str = r#"{"response":{"GeoObjectCollection":{"featureMember":[]}}}"#;
+
+    fn geocoder(server: &StubServer) -> Arc<YandexGeocoder> {
+        YandexGeocoder::new(server.url.clone(), "key".to_owned(), Duration::from_secs(5))
+    }
+
+    #[tokio::test]
+    async fn finds_cities_by_name() {
+        let server = StubServer::start(vec![(200, TVER)]).await;
+
+        let cities = geocoder(&server).cities_by_name("Тверь").await.unwrap();
+
+        let labels: Vec<_> = cities.iter().map(GeocodedCity::label).collect();
+        assert_eq!(labels, ["Тверь, Тверская область", "Тверь, Курская область"]);
+        let coordinates = cities[0].coordinates.as_ref().unwrap();
+        assert_eq!((*coordinates.longitude(), *coordinates.latitude()), (35.911896, 56.859611));
+
+        let request = &server.requests()[0];
+        assert_eq!(query(request, "geocode").as_deref(), Some("Тверь"));
+        assert_eq!(query(request, "apikey").as_deref(), Some("key"));
+        assert_eq!(query(request, "results").as_deref(), Some("5"));
+        assert_eq!(query(request, "kind"), None);
+    }
+
+    #[tokio::test]
+    async fn finds_city_by_coordinates() {
+        let server = StubServer::start(vec![(200, KHIMKI)]).await;
+
+        let city = geocoder(&server).city_by_coordinates(&Coordinates::new(37.43, 55.9)).await.unwrap();
+
+        assert_eq!(city.label(), "Химки, Московская область");
+        let request = &server.requests()[0];
+        assert_eq!(query(request, "geocode").as_deref(), Some("37.43,55.9"));
+        assert_eq!(query(request, "kind").as_deref(), Some("locality"));
+        assert_eq!(query(request, "results").as_deref(), Some("1"));
+    }
+
+    #[tokio::test]
+    async fn reports_unknown_cities() {
+        let server = StubServer::start(vec![(200, NOTHING), (200, NOTHING)]).await;
+        let geocoder = geocoder(&server);
+
+        assert!(matches!(
+            geocoder.cities_by_name("Абырвалг").await,
+            Err(FetchingError::CityNotFound { name }) if name == "Абырвалг"
+        ));
+        assert!(matches!(
+            geocoder.city_by_coordinates(&Coordinates::new(-30.0, 0.0)).await,
+            Err(FetchingError::CityNotFound { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn reports_rate_limits_and_server_errors() {
+        let server = StubServer::start(vec![(429, "{}"), (503, "{}"), (403, "{}")]).await;
+        let geocoder = geocoder(&server);
+
+        for expected in [429, 503, 403] {
+            match geocoder.cities_by_name("Тверь").await {
+                Err(error @ FetchingError::UnsuccessfulRequest { response_code }) => {
+                    assert_eq!(response_code, expected);
+                    assert_eq!(error.is_transient(), expected != 403);
+                }
+                result => panic!("unexpected result {result:?}"),
+            }
+        }
+    }
+}